@@ -1,7 +1,20 @@
+use std::any::type_name;
+use std::cell::{Cell, RefCell};
 use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use crate::error::Error;
-use crate::value::Node;
+use crate::dotenv;
+use crate::error::{Error, ErrorKind};
+use crate::interpolate::Expander;
+use crate::layers::{Layer, Layers};
+use crate::list::{self, ListEscape};
+use crate::secret;
+use crate::suggest;
+use crate::value::{self, Node, Value};
+use serde::de::value::SeqDeserializer;
 use serde::de::{DeserializeSeed, IntoDeserializer, SeqAccess, Visitor};
 use serde::{de, forward_to_deserialize_any};
 
@@ -31,8 +44,9 @@ pub fn from_env<T>() -> Result<T, Error>
 where
     T: de::DeserializeOwned,
 {
-    T::deserialize(Deserializer(Node::from_env()))
+    EnvDeserializer::new().from_env()
 }
+
 /// Deserialize into struct via env with a prefix.
 ///
 /// # Examples
@@ -66,7 +80,7 @@ pub fn from_env_with_prefix<T>(prefix: &str) -> Result<T, Error>
 where
     T: de::DeserializeOwned,
 {
-    T::deserialize(Deserializer(Node::from_env_with_prefix(prefix)))
+    EnvDeserializer::new().prefix(prefix).from_env()
 }
 
 /// Deserialize into struct via an iterable of `(AsRef<str>, AsRef<str>)`
@@ -103,7 +117,7 @@ where
     S: AsRef<str>,
     T: de::DeserializeOwned,
 {
-    T::deserialize(Deserializer(Node::from_iter(iter)))
+    EnvDeserializer::new().from_iter(iter)
 }
 
 /// Deserialize into struct via an iterable of `(AsRef<str>, AsRef<str>)`
@@ -140,567 +154,2557 @@ where
     S: AsRef<str>,
     T: de::DeserializeOwned,
 {
-    T::deserialize(Deserializer(Node::from_iter_with_prefix(iter, prefix)))
+    EnvDeserializer::new().prefix(prefix).from_iter(iter)
 }
 
-struct Deserializer(Node);
+/// Deserialize into struct via the content of a `.env` file.
+///
+/// The process env is not read, see [`EnvDeserializer::merge_env`] to merge
+/// them.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_env::from_dotenv_str;
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct Test {
+///     home: String,
+///     motd: String,
+/// }
+/// let content = r#"
+/// ## comment
+/// export HOME=/test
+/// MOTD="hello
+/// world"
+/// "#;
+///
+/// let actual: Test = from_dotenv_str(content).expect("deserialize from dotenv");
+///
+/// let expected = Test {
+///     home: "/test".to_string(),
+///     motd: "hello\nworld".to_string(),
+/// };
+///
+/// assert_eq!(actual, expected);
+/// ```
+pub fn from_dotenv_str<T>(content: &str) -> Result<T, Error>
+where
+    T: de::DeserializeOwned,
+{
+    EnvDeserializer::new().from_dotenv_str(content)
+}
 
-impl<'de> de::Deserializer<'de> for Deserializer {
-    type Error = Error;
+/// Deserialize into struct via a `.env` file.
+///
+/// The process env is not read, see [`EnvDeserializer::merge_env`] to merge
+/// them.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_env::from_dotenv_file;
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct Test {
+///     home: String,
+/// }
+/// let path = std::env::temp_dir().join("serde-env-from-dotenv-file.env");
+/// std::fs::write(&path, "HOME=/test\n").expect("write dotenv");
+///
+/// let actual: Test = from_dotenv_file(&path).expect("deserialize from dotenv");
+///
+/// assert_eq!(actual, Test { home: "/test".to_string() });
+/// ```
+pub fn from_dotenv_file<T>(path: impl AsRef<Path>) -> Result<T, Error>
+where
+    T: de::DeserializeOwned,
+{
+    EnvDeserializer::new().from_dotenv_file(path)
+}
 
-    fn deserialize_any<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_str(self.0.value())
+/// Parse the content of a `.env` file into env.
+fn dotenv_vars(content: &str) -> Result<Vec<(String, Value)>, Error> {
+    Ok(dotenv::parse(content)?
+        .into_iter()
+        .map(|(k, v)| (k, Value::from(v)))
+        .collect())
+}
+
+/// Read a `.env` file into env, naming the file in syntax errors.
+fn dotenv_file_vars(path: &Path) -> Result<Vec<(String, Value)>, Error> {
+    let content = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+    dotenv_vars(&content).map_err(|err| err.with_hint(format_args!("in `{}`", path.display())))
+}
+
+/// How env that is set to empty like `FOO=` is treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyValues {
+    /// Treat it as unset, so `Option<String>` is `None`.
+    #[default]
+    Unset,
+    /// Keep it as empty, so `Option<String>` is `Some("")` and
+    /// `Option<Vec<String>>` is `Some(vec![])`.
+    Empty,
+    /// Return an error when a field reads it, other env that is set to
+    /// empty is ignored.
+    Error,
+}
+
+/// Builder to configure how env is deserialized.
+///
+/// The free functions like [`from_env`] and [`from_iter_with_prefix`] are
+/// shortcuts for the default options of this builder.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_env::EnvDeserializer;
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct Db {
+///     hosts: Vec<String>,
+///     max_conns: u32,
+/// }
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct Test {
+///     db: Db,
+/// }
+/// let vars = [
+///     ("APP__DB__HOSTS", "a;b"),
+///     ("APP__DB__MAX_CONNS", "10"),
+/// ];
+///
+/// let actual: Test = EnvDeserializer::new()
+///     .prefix("APP")
+///     .separator("__")
+///     .list_delimiter(";")
+///     .from_iter(vars)
+///     .expect("deserialize from iter");
+///
+/// let expected = Test {
+///     db: Db {
+///         hosts: vec!["a".to_string(), "b".to_string()],
+///         max_conns: 10,
+///     },
+/// };
+///
+/// assert_eq!(actual, expected);
+/// ```
+#[derive(Debug, Clone)]
+pub struct EnvDeserializer {
+    prefix: Option<String>,
+    separator: String,
+    list_delimiters: Vec<String>,
+    list_escape: ListEscape,
+    trim_list_elements: bool,
+    keep_empty_list_elements: Option<bool>,
+    case_sensitive: bool,
+    empty_values: EmptyValues,
+    null_values: Vec<String>,
+    collect_errors: bool,
+    secret_patterns: Vec<String>,
+    secrets: Vec<String>,
+    merge_env: bool,
+    interpolate: bool,
+    file_vars: bool,
+    deny_unused: bool,
+    infer_types: bool,
+}
+
+impl Default for EnvDeserializer {
+    fn default() -> Self {
+        Self {
+            prefix: None,
+            separator: "_".to_string(),
+            list_delimiters: vec![",".to_string(), ":".to_string()],
+            list_escape: ListEscape::None,
+            trim_list_elements: true,
+            keep_empty_list_elements: None,
+            case_sensitive: false,
+            empty_values: EmptyValues::Unset,
+            null_values: Vec::new(),
+            collect_errors: false,
+            secret_patterns: secret::DEFAULT_PATTERNS.map(String::from).to_vec(),
+            secrets: Vec::new(),
+            merge_env: false,
+            interpolate: false,
+            file_vars: false,
+            deny_unused: false,
+            infer_types: false,
+        }
     }
+}
 
-    fn deserialize_bool<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_bool(self.0.value().parse().map_err(Error::new)?)
+impl EnvDeserializer {
+    /// Create a new builder with default options.
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    fn deserialize_i8<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_i8(self.0.value().parse().map_err(Error::new)?)
+    /// Only read env starting with `prefix` followed by the separator.
+    ///
+    /// The prefix itself is stripped from the key before deserializing. It's
+    /// matched with the same case rule as keys, see
+    /// [`EnvDeserializer::case_sensitive`].
+    ///
+    /// If no env matches the prefix and deserializing fails, the error
+    /// mentions the prefix. It's an error even if deserializing succeeds
+    /// under [`EnvDeserializer::deny_unused`].
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
     }
 
-    fn deserialize_i16<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_i16(self.0.value().parse().map_err(Error::new)?)
+    /// Set the separator used to split keys into nested fields.
+    ///
+    /// The prefix is joined with the rest of the key by the separator as
+    /// well.
+    ///
+    /// Default to `_`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_env::EnvDeserializer;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// struct Db {
+    ///     max_conns: u32,
+    /// }
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// struct Test {
+    ///     db: Db,
+    /// }
+    ///
+    /// let actual: Test = EnvDeserializer::new()
+    ///     .prefix("APP")
+    ///     .separator("__")
+    ///     .from_iter([("APP__DB__MAX_CONNS", "10")])
+    ///     .expect("deserialize from iter");
+    ///
+    /// assert_eq!(actual, Test { db: Db { max_conns: 10 } });
+    /// ```
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
     }
 
-    fn deserialize_i32<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_i32(self.0.value().parse().map_err(Error::new)?)
+    /// Set the delimiter used to split values into sequences and tuples.
+    ///
+    /// A delimiter made of whitespace only like `" "` splits on any run of
+    /// whitespace.
+    ///
+    /// Default to `,`.
+    pub fn list_delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        match self.list_delimiters.first_mut() {
+            Some(outer) => *outer = delimiter.into(),
+            None => self.list_delimiters.push(delimiter.into()),
+        }
+        self
     }
 
-    fn deserialize_i64<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    /// Set the delimiters used to split values into sequences and tuples
+    /// for each nesting level, starting from the outermost one.
+    ///
+    /// For example, with `[";", ","]`, `1,2;3,4` is deserialized into
+    /// `Vec<Vec<u32>>` as `[[1, 2], [3, 4]]`.
+    ///
+    /// Default to `[",", ":"]`, so `a:80,b:81` is deserialized into
+    /// `Vec<(String, u16)>` as `[("a", 80), ("b", 81)]`.
+    pub fn list_delimiters<I, S>(mut self, delimiters: I) -> Self
     where
-        V: Visitor<'de>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
     {
-        vis.visit_i64(self.0.value().parse().map_err(Error::new)?)
+        self.list_delimiters = delimiters.into_iter().map(Into::into).collect();
+        self
     }
 
-    fn deserialize_u8<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_u8(self.0.value().parse().map_err(Error::new)?)
+    /// Set how a literal delimiter is escaped inside list elements.
+    ///
+    /// Default to [`ListEscape::None`].
+    pub fn list_escape(mut self, escape: ListEscape) -> Self {
+        self.list_escape = escape;
+        self
     }
 
-    fn deserialize_u16<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_u16(self.0.value().parse().map_err(Error::new)?)
+    /// Set whether whitespace around list elements is trimmed.
+    ///
+    /// Default to `true`.
+    pub fn trim_list_elements(mut self, trim: bool) -> Self {
+        self.trim_list_elements = trim;
+        self
     }
 
-    forward_to_deserialize_any! {
-        unit unit_struct
-        tuple_struct ignored_any
+    /// Set whether empty list elements like the middle one of `a,,b` are
+    /// kept.
+    ///
+    /// By default, sequences drop empty elements while tuples keep them so
+    /// that positions are preserved.
+    pub fn keep_empty_list_elements(mut self, keep: bool) -> Self {
+        self.keep_empty_list_elements = Some(keep);
+        self
     }
 
-    fn deserialize_u32<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_u32(self.0.value().parse().map_err(Error::new)?)
+    /// Set whether keys are case sensitive.
+    ///
+    /// By default, keys are lowercased, so `PATH`, `Path` and `path` are the
+    /// same key, and struct fields match them whatever their rename casing.
+    /// Field names are split into words as well, so `DATABASE_URL`,
+    /// `databaseUrl` and `database-url` all match `APP_DATABASE_URL`. In case
+    /// sensitive mode, keys keep their original text, both in struct fields
+    /// and map keys, and must match fields exactly.
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
     }
 
-    fn deserialize_u64<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_u64(self.0.value().parse().map_err(Error::new)?)
+    /// Set how env that is set to empty like `FOO=` is treated.
+    ///
+    /// Default to [`EmptyValues::Unset`].
+    pub fn empty_values(mut self, empty_values: EmptyValues) -> Self {
+        self.empty_values = empty_values;
+        self
     }
 
-    fn deserialize_f32<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    /// Treat env whose value is one of `values` like `null` or `none` as
+    /// unset, compared case insensitively.
+    ///
+    /// Default to no values.
+    pub fn null_values<I, S>(mut self, values: I) -> Self
     where
-        V: Visitor<'de>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
     {
-        vis.visit_f32(self.0.value().parse().map_err(Error::new)?)
+        self.null_values = values.into_iter().map(Into::into).collect();
+        self
     }
 
-    fn deserialize_f64<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_f64(self.0.value().parse().map_err(Error::new)?)
+    /// Set whether to keep going after an error and report all of them.
+    ///
+    /// Fields that failed are treated as unset, so the ones with
+    /// `#[serde(default)]` or `Option` fall back to their defaults while the
+    /// rest is deserialized. A required field that failed makes its struct
+    /// fail as well, and since serde stops at the first missing field, only
+    /// that one is reported for a struct. No values are made up for fields
+    /// to go on with. serde reads `#[serde(flatten)]` fields after checking
+    /// the other fields, so their errors are only found once those are set.
+    /// All errors are then returned together as [`ErrorKind::Multiple`], or
+    /// alone if there is only one.
+    ///
+    /// Default to `false`, which returns the first error.
+    pub fn collect_errors(mut self, collect: bool) -> Self {
+        self.collect_errors = collect;
+        self
     }
 
-    fn deserialize_char<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    /// Set patterns of env names whose values are secret, so they are
+    /// redacted in errors.
+    ///
+    /// Patterns are matched with the full env name including the prefix,
+    /// case insensitively, where `*` matches any characters.
+    ///
+    /// Default to `["*PASSWORD*", "*TOKEN*", "*SECRET*"]`.
+    pub fn secret_patterns<I, S>(mut self, patterns: I) -> Self
     where
-        V: Visitor<'de>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
     {
-        vis.visit_char(self.0.value().parse().map_err(Error::new)?)
+        self.secret_patterns = patterns.into_iter().map(Into::into).collect();
+        self
     }
 
-    fn deserialize_str<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    /// Mark env like `APP_DB_URL` as secret in addition to the ones matching
+    /// [`EnvDeserializer::secret_patterns`], compared case insensitively.
+    ///
+    /// Default to no env.
+    pub fn secrets<I, S>(mut self, names: I) -> Self
     where
-        V: Visitor<'de>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
     {
-        vis.visit_str(self.0.value())
+        self.secrets = names.into_iter().map(Into::into).collect();
+        self
     }
 
-    fn deserialize_string<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_string(self.0.into_value())
+    /// Set whether `.env` files are merged with the process env, which takes
+    /// precedence over the files.
+    ///
+    /// Default to `false`, so only the files are read.
+    pub fn merge_env(mut self, merge: bool) -> Self {
+        self.merge_env = merge;
+        self
     }
 
-    fn deserialize_bytes<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_bytes(self.0.value().as_bytes())
+    /// Set whether references to other env in values are expanded.
+    ///
+    /// `$NAME` and `${NAME}` are replaced by the value of `NAME`,
+    /// `${NAME:-default}` falls back to `default` and `${NAME:?message}`
+    /// fails with `message` if `NAME` is unset or empty. `$$` is a literal
+    /// `$`. References are looked up in the same input, including env
+    /// outside the prefix, and expanded recursively. Undefined references
+    /// and cycles are reported naming the env that contains them.
+    ///
+    /// Values are expanded when a field reads them, so env that no field
+    /// reads, like unrelated shell variables, are never expanded.
+    ///
+    /// Default to `false`, so values are kept as is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_env::EnvDeserializer;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// struct Test {
+    ///     url: String,
+    ///     price: String,
+    /// }
+    ///
+    /// let vars = [
+    ///     ("DB_HOST", "db"),
+    ///     ("APP_URL", "postgres://${DB_HOST}:${DB_PORT:-5432}/app"),
+    ///     ("APP_PRICE", "$$5"),
+    /// ];
+    /// let actual: Test = EnvDeserializer::new()
+    ///     .prefix("APP")
+    ///     .interpolate(true)
+    ///     .from_iter(vars)
+    ///     .expect("deserialize from iter");
+    ///
+    /// assert_eq!(actual.url, "postgres://db:5432/app");
+    /// assert_eq!(actual.price, "$5");
+    /// ```
+    pub fn interpolate(mut self, interpolate: bool) -> Self {
+        self.interpolate = interpolate;
+        self
     }
 
-    fn deserialize_byte_buf<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_byte_buf(self.0.into_value().into_bytes())
+    /// Set whether a struct field is read from the file named by
+    /// `<NAME>_FILE` if its env `<NAME>` is not set, like Docker secrets.
+    ///
+    /// The content of the file is used as the value with a trailing newline
+    /// trimmed, and it's treated as secret like the env matching
+    /// [`EnvDeserializer::secret_patterns`]. Setting both `<NAME>` and
+    /// `<NAME>_FILE` is an error. Structs that have a field for
+    /// `<NAME>_FILE` itself keep reading it as is.
+    ///
+    /// Default to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_env::EnvDeserializer;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// struct Db {
+    ///     user: String,
+    ///     password: String,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("serde-env-file-vars-password");
+    /// std::fs::write(&path, "secret\n").expect("write password");
+    ///
+    /// let vars = [
+    ///     ("DB_USER", "admin"),
+    ///     ("DB_PASSWORD_FILE", path.to_str().expect("path is unicode")),
+    /// ];
+    /// let actual: Db = EnvDeserializer::new()
+    ///     .prefix("DB")
+    ///     .file_vars(true)
+    ///     .from_iter(vars)
+    ///     .expect("deserialize from iter");
+    ///
+    /// assert_eq!(actual.password, "secret");
+    /// ```
+    pub fn file_vars(mut self, file_vars: bool) -> Self {
+        self.file_vars = file_vars;
+        self
     }
 
-    fn deserialize_option<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        if self.0.is_empty() {
-            vis.visit_none()
-        } else {
-            vis.visit_some(Deserializer(self.0))
-        }
+    /// Set whether env under the prefix that no field reads is an error.
+    ///
+    /// All unused env are reported, so typos like `APP_DATABSE_URL` don't go
+    /// unnoticed. Without prefix every env is checked, which is rarely
+    /// useful with [`EnvDeserializer::from_env`]. See
+    /// [`EnvDeserializer::from_env_with_unused`] to get them without failing.
+    ///
+    /// With a prefix, it's also an error if no env matches the prefix at
+    /// all, which is likely a wrong prefix rather than all fields left to
+    /// their defaults.
+    ///
+    /// serde buffers the env of structs with `#[serde(flatten)]` fields
+    /// that the struct doesn't declare itself, without telling which of it
+    /// the flattened fields read. Such env can't be checked, so it's an
+    /// error as well.
+    ///
+    /// Default to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_env::{EnvDeserializer, ErrorKind};
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Test {
+    ///     #[serde(default)]
+    ///     database_url: String,
+    /// }
+    ///
+    /// let err = EnvDeserializer::new()
+    ///     .prefix("APP")
+    ///     .deny_unused(true)
+    ///     .from_iter::<_, _, Test>([("APP_DATABSE_URL", "postgres://db")])
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(err.kind(), ErrorKind::UnknownField);
+    /// assert_eq!(err.key(), Some("APP_DATABSE_URL"));
+    /// ```
+    pub fn deny_unused(mut self, deny: bool) -> Self {
+        self.deny_unused = deny;
+        self
     }
 
-    fn deserialize_newtype_struct<V>(
-        self,
-        _name: &'static str,
-        vis: V,
-    ) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_newtype_struct(Deserializer(self.0))
+    /// Set whether values read without a known type, like the leaves of
+    /// `serde_json::Value` or fields of `#[serde(untagged)]` enums, are
+    /// inferred as booleans and numbers.
+    ///
+    /// `true` and `false` become booleans, integers and finite floats become
+    /// numbers, and other values stay strings.
+    ///
+    /// Default to `false`, so they are all strings.
+    ///
+    /// serde reads the env of structs with `#[serde(flatten)]` fields
+    /// without known types as well, by their full names after the prefix
+    /// like `max_conns` for `APP_MAX_CONNS`, and can't parse strings into
+    /// the types of the flattened fields later. So flattened fields that are
+    /// not strings need this, while a flattened `HashMap<String, String>`
+    /// takes every value as is without it. Nested structs can't be read
+    /// inside such structs, as their env is only known by the full names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_env::EnvDeserializer;
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// #[serde(untagged)]
+    /// enum Backend {
+    ///     Redis { url: String, db: u8 },
+    ///     File { path: String },
+    /// }
+    ///
+    /// #[derive(Debug, Deserialize, PartialEq)]
+    /// struct Test {
+    ///     backend: Backend,
+    /// }
+    ///
+    /// let vars = [("BACKEND_URL", "redis://localhost"), ("BACKEND_DB", "1")];
+    /// let actual: Test = EnvDeserializer::new()
+    ///     .infer_types(true)
+    ///     .from_iter(vars)
+    ///     .expect("deserialize from iter");
+    ///
+    /// let expected = Backend::Redis {
+    ///     url: "redis://localhost".to_string(),
+    ///     db: 1,
+    /// };
+    /// assert_eq!(actual.backend, expected);
+    /// ```
+    pub fn infer_types(mut self, infer: bool) -> Self {
+        self.infer_types = infer;
+        self
     }
 
-    fn deserialize_seq<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        let elements = self
-            .0
-            .value()
-            .split(',')
-            .map(|v| v.trim().to_string())
-            .filter(|v| !v.is_empty())
-            .collect();
+    /// Check whether env should be kept in the tree.
+    ///
+    /// Env that is set to empty is kept under [`EmptyValues::Error`], so
+    /// that it's rejected only when a field reads it.
+    fn keep_env(&self, v: &Value) -> bool {
+        if let Some(v) = v.as_str() {
+            if self.null_values.iter().any(|n| n.eq_ignore_ascii_case(v)) {
+                return false;
+            }
+        }
+        if !v.is_empty() {
+            return true;
+        }
 
-        vis.visit_seq(SeqAccessor::new(elements))
+        match self.empty_values {
+            EmptyValues::Unset => false,
+            EmptyValues::Empty | EmptyValues::Error => true,
+        }
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, vis: V) -> Result<V::Value, Self::Error>
+    /// Deserialize into struct via env.
+    ///
+    /// Env is read via [`env::vars_os`], so env that is not valid unicode
+    /// doesn't panic:
+    ///
+    /// - Keys that are not valid unicode are skipped, or reported as an
+    ///   error if they are under the prefix. Without a prefix, they are
+    ///   always skipped, as they can't be meant for any field.
+    /// - Values that are not valid unicode are kept as is, they can be
+    ///   deserialized into `OsString` and byte buffers, and report an error
+    ///   naming the env for other types. serde only accepts unicode for
+    ///   `PathBuf`, so use `OsString` for paths that may be not.
+    pub fn from_env<T>(&self) -> Result<T, Error>
     where
-        V: Visitor<'de>,
+        T: de::DeserializeOwned,
     {
-        let elements = self
-            .0
-            .value()
-            .split(',')
-            .map(|v| v.trim().to_string())
-            .collect();
-
-        vis.visit_seq(SeqAccessor::new(elements))
+        self.deserialize(self.env_vars()?)
     }
 
-    fn deserialize_map<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    /// Deserialize into struct via env like [`EnvDeserializer::from_env`],
+    /// and return the names of env under the prefix that no field reads.
+    pub fn from_env_with_unused<T>(&self) -> Result<(T, Vec<String>), Error>
     where
-        V: Visitor<'de>,
+        T: de::DeserializeOwned,
     {
-        let keys = self.0.flatten("");
-        vis.visit_map(MapAccessor::new(keys, self.0))
+        self.deserialize_with_unused(self.env_vars()?)
     }
 
-    fn deserialize_struct<V>(
-        self,
-        _name: &'static str,
-        fields: &'static [&'static str],
-        vis: V,
-    ) -> Result<V::Value, Self::Error>
+    /// Deserialize into struct via the content of a `.env` file.
+    ///
+    /// The process env is merged if [`EnvDeserializer::merge_env`] is set.
+    pub fn from_dotenv_str<T>(&self, content: &str) -> Result<T, Error>
     where
-        V: Visitor<'de>,
+        T: de::DeserializeOwned,
     {
-        let keys = fields.iter().map(|v| v.to_string()).collect();
-
-        vis.visit_map(MapAccessor::new(keys, self.0))
+        self.deserialize_dotenv(dotenv_vars(content)?)
     }
 
-    fn deserialize_identifier<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    /// Deserialize into struct via a `.env` file.
+    ///
+    /// The process env is merged if [`EnvDeserializer::merge_env`] is set.
+    pub fn from_dotenv_file<T>(&self, path: impl AsRef<Path>) -> Result<T, Error>
     where
-        V: Visitor<'de>,
+        T: de::DeserializeOwned,
     {
-        self.deserialize_string(vis)
+        self.deserialize_dotenv(dotenv_file_vars(path.as_ref())?)
     }
 
-    fn deserialize_enum<V>(
-        self,
-        _name: &'static str,
-        variants: &'static [&'static str],
-        vis: V,
-    ) -> Result<V::Value, Self::Error>
+    /// Deserialize into struct via env from [`Layers`] merged in order.
+    ///
+    /// Later layers override earlier ones key by key, and the options like
+    /// the prefix apply to the merged env as a whole.
+    pub fn from_layers<T>(&self, layers: &Layers) -> Result<T, Error>
     where
-        V: Visitor<'de>,
+        T: de::DeserializeOwned,
     {
-        let keys = variants.iter().map(|v| v.to_string()).collect();
+        let mut vars = Vec::new();
+        for layer in &layers.layers {
+            match layer {
+                Layer::Vars(layer) => vars.extend(
+                    layer
+                        .iter()
+                        .map(|(k, v)| (k.clone(), Value::from(v.as_str()))),
+                ),
+                Layer::DotenvStr(content) => vars.extend(dotenv_vars(content)?),
+                Layer::DotenvFile(path) => vars.extend(dotenv_file_vars(path)?),
+                Layer::Env => vars.extend(self.env_vars()?),
+            }
+        }
 
-        vis.visit_enum(EnumAccessor::new(keys, self.0))
+        // Later env overrides earlier one with the same key.
+        self.deserialize(vars)
     }
-}
 
-/// KeyDeserializer is used to deserialize key of a map/enum/struct.
-///
-/// It's similar to `StringDeserializer`, but only used for key deserialization in serde-env where:
-///
-/// - Key is case insensitive: `PATH`, `Path`, `path`, `PatH` all map to the same key.
-/// - Key is prefix based: Key could be adapted based on the deserialize target itself.
-struct KeyDeserializer {
-    key: String,
-}
-
-impl KeyDeserializer {
-    fn new(key: String) -> Self {
-        Self { key }
+    /// Deserialize env read from `.env` files, merged with the process env
+    /// if [`EnvDeserializer::merge_env`] is set.
+    fn deserialize_dotenv<T>(&self, vars: Vec<(String, Value)>) -> Result<T, Error>
+    where
+        T: de::DeserializeOwned,
+    {
+        if self.merge_env {
+            // Later env overrides earlier one with the same key.
+            self.deserialize(vars.into_iter().chain(self.env_vars()?))
+        } else {
+            self.deserialize(vars)
+        }
     }
-}
 
-impl<'de> de::Deserializer<'de> for KeyDeserializer {
-    type Error = Error;
+    /// Read the process env.
+    fn env_vars(&self) -> Result<Vec<(String, Value)>, Error> {
+        let mut invalid_key = None;
+        let vars: Vec<_> = env::vars_os()
+            .filter_map(|(k, v)| match k.into_string() {
+                Ok(k) => {
+                    let v = Value::from_os(&k, v);
+                    Some((k, v))
+                }
+                Err(k) => {
+                    let k = k.to_string_lossy();
+                    if self.prefix.is_some() && self.matches_prefix(&k) {
+                        invalid_key.get_or_insert_with(|| k.into_owned());
+                    }
+                    None
+                }
+            })
+            .collect();
+        if let Some(k) = invalid_key {
+            return Err(
+                Error::new(ErrorKind::Custom, "name is not valid unicode").with_env(k, None)
+            );
+        }
 
-    fn deserialize_any<V>(self, vis: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        vis.visit_str(&self.key)
+        Ok(vars)
     }
 
-    fn deserialize_str<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    /// Deserialize into struct via an iterable of `(AsRef<str>, AsRef<str>)`
+    /// representing keys and values.
+    pub fn from_iter<Iter, S, T>(&self, iter: Iter) -> Result<T, Error>
     where
-        V: Visitor<'de>,
+        Iter: IntoIterator<Item = (S, S)>,
+        S: AsRef<str>,
+        T: de::DeserializeOwned,
     {
-        vis.visit_str(&self.key)
+        self.deserialize(iter.into_iter().map(|(k, v)| (k, Value::from(v.as_ref()))))
     }
 
-    fn deserialize_string<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    /// Deserialize into struct via an iterable like
+    /// [`EnvDeserializer::from_iter`], and return the names of env under the
+    /// prefix that no field reads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use serde_env::EnvDeserializer;
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Test {
+    ///     port: u16,
+    /// }
+    ///
+    /// let vars = [("APP_PORT", "80"), ("APP_PROT", "tcp"), ("HOME", "/home")];
+    /// let (actual, unused): (Test, _) = EnvDeserializer::new()
+    ///     .prefix("APP")
+    ///     .from_iter_with_unused(vars)
+    ///     .expect("deserialize from iter");
+    ///
+    /// assert_eq!(actual.port, 80);
+    /// assert_eq!(unused, ["APP_PROT"]);
+    /// ```
+    pub fn from_iter_with_unused<Iter, S, T>(&self, iter: Iter) -> Result<(T, Vec<String>), Error>
     where
-        V: Visitor<'de>,
+        Iter: IntoIterator<Item = (S, S)>,
+        S: AsRef<str>,
+        T: de::DeserializeOwned,
     {
-        vis.visit_string(self.key)
+        self.deserialize_with_unused(iter.into_iter().map(|(k, v)| (k, Value::from(v.as_ref()))))
     }
 
-    fn deserialize_enum<V>(
-        self,
-        _name: &'static str,
-        variants: &'static [&'static str],
-        visitor: V,
-    ) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        for v in variants {
-            // Return the enum variant if it matches the key.
-            if self.key.eq_ignore_ascii_case(v) {
-                return visitor.visit_enum(v.into_deserializer());
-            }
+    /// Check if key matches the prefix, always `true` without prefix.
+    fn matches_prefix(&self, k: &str) -> bool {
+        match &self.prefix {
+            None => true,
+            Some(prefix) => value::strip_prefix(
+                k,
+                &format!("{prefix}{}", self.separator),
+                self.case_sensitive,
+            )
+            .is_some(),
         }
-        Err(de::Error::unknown_variant(&self.key, variants))
     }
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char bytes byte_buf
-        option unit unit_struct newtype_struct seq tuple
-        tuple_struct map struct identifier ignored_any
+    /// Join the path of a node with the key of its child.
+    fn join_path(&self, path: &str, key: &str) -> String {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}{}{key}", self.separator)
+        }
     }
-}
-
-struct SeqAccessor {
-    elements: std::vec::IntoIter<String>,
-}
 
-impl SeqAccessor {
-    fn new(keys: Vec<String>) -> Self {
-        Self {
-            elements: keys.into_iter(),
+    /// Get the key of a struct field in the tree.
+    ///
+    /// Field names are split into lowercase words joined by `_` unless keys
+    /// are case sensitive, so that renames like `DATABASE_URL`, `logLevel`
+    /// or `log-level` match env like `LOG_LEVEL`.
+    fn field_key(&self, field: &str) -> String {
+        if self.case_sensitive {
+            field.to_string()
+        } else {
+            value::field_words(field)
         }
     }
-}
 
-impl<'de> SeqAccess<'de> for SeqAccessor {
-    type Error = Error;
-
-    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
-    where
-        T: DeserializeSeed<'de>,
-    {
-        match self.elements.next() {
-            None => Ok(None),
-            Some(v) => Ok(Some(seed.deserialize(Deserializer(Node::new(v)))?)),
+    /// Get the env name of a path, which is uppercased unless keys are case
+    /// sensitive.
+    fn env_name(&self, path: &str) -> String {
+        if self.case_sensitive {
+            path.to_string()
+        } else {
+            path.to_uppercase()
         }
     }
-}
 
-struct MapAccessor {
-    last_value: Option<Node>,
-    keys: std::collections::btree_set::IntoIter<String>,
-    node: Node,
-}
+    /// Check if the value of env is secret, which includes values read from
+    /// files.
+    fn is_secret(&self, state: &State, name: &str) -> bool {
+        state.file_vars.borrow().contains(name)
+            || self.secrets.iter().any(|s| s.eq_ignore_ascii_case(name))
+            || self
+                .secret_patterns
+                .iter()
+                .any(|p| secret::matches(p, name))
+    }
 
-impl MapAccessor {
-    fn new(keys: BTreeSet<String>, node: Node) -> Self {
-        Self {
-            last_value: None,
-            keys: keys.into_iter(),
-            node,
+    /// Keep the value of node at `path` if it's secret, so that it can be
+    /// redacted from errors after the node is consumed.
+    fn secret_value(&self, state: &State, path: &str, node: &Node) -> Option<String> {
+        let secret = !path.is_empty() && self.is_secret(state, &self.env_name(path));
+        secret.then(|| node.value().to_string())
+    }
+
+    /// Name the env at `path` in the error if it doesn't name one yet.
+    ///
+    /// `value` is kept in the error, or redacted from it if the env is
+    /// secret. Errors that may carry a value must go through here.
+    fn env_error(&self, state: &State, err: Error, path: &str, value: Option<&str>) -> Error {
+        if err.key().is_some() {
+            return err;
+        }
+        let key = self.env_name(path);
+        if self.is_secret(state, &key) {
+            err.with_env(key, None).redact(value)
+        } else {
+            err.with_env(key, value)
         }
     }
-}
 
-impl<'de> de::MapAccess<'de> for MapAccessor {
-    type Error = Error;
+    /// Name missing or unknown fields after the env they are expected in.
+    ///
+    /// If a field is missing because it failed in a previous attempt, the
+    /// struct at `path` fails as well, so that its parent treats it as unset
+    /// in the next attempt.
+    fn field_error(&self, state: &State, path: &str, err: Error) -> Error {
+        let err = match err.field() {
+            // Named by a nested struct already.
+            Some(_) if err.key().is_some() => err,
+            Some(field) => {
+                let max_distance = (field.chars().count() / 3).clamp(1, 2);
+                let path = self.join_path(path, &self.field_key(field));
+                let err = self.env_error(state, err, &path, None);
+                match err.kind() {
+                    ErrorKind::MissingField => self.suggest_env(state, err, max_distance),
+                    _ => err,
+                }
+            }
+            None => return err,
+        };
 
-    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
-    where
-        K: DeserializeSeed<'de>,
-    {
-        debug_assert!(
-            self.last_value.is_none(),
-            "value for the last entry is not deserialized"
-        );
+        let root = self.prefix.as_deref().unwrap_or_default();
+        if let (ErrorKind::MissingField, Some(k)) = (err.kind(), err.key()) {
+            if path != root && state.failed.borrow().contains(k) {
+                state.failed.borrow_mut().insert(self.env_name(path));
+            }
+        }
+        err
+    }
 
-        loop {
-            let key = match self.keys.next() {
-                None => return Ok(None),
-                Some(v) => v,
+    /// Name the env of a value that serde buffered for a flattened field in
+    /// the error, which is the only env under the map at `path` with the
+    /// value.
+    fn buffered_error(&self, state: &State, node: &Node, path: &str, err: Error) -> Error {
+        let v = match err.value() {
+            Some(v) if err.key().is_none() => v.to_string(),
+            _ => return err,
+        };
+        let sep = &self.separator;
+        let mut matched = node.flatten("", sep).into_iter().filter(|k| {
+            node.get(k, sep)
+                .and_then(Node::raw_value)
+                .and_then(Value::as_str)
+                == Some(v.as_str())
+        });
+        match (matched.next(), matched.next()) {
+            (Some(k), None) => self.env_error(state, err, &self.join_path(path, &k), Some(&v)),
+            _ => err,
+        }
+    }
+
+    /// Suggest the unused env that is likely meant for the missing env named
+    /// in the error.
+    fn suggest_env(&self, state: &State, err: Error, max_distance: usize) -> Error {
+        let used = state.used.borrow();
+        let names = state
+            .names
+            .iter()
+            .filter(|k| !used.contains(*k))
+            .map(String::as_str);
+        let suggestion = err
+            .key()
+            .and_then(|key| suggest::suggest(key, names, &self.separator, max_distance));
+
+        match suggestion {
+            Some((name, suggested)) => {
+                err.with_hint(format_args!("`{name}` is set, did you mean `{suggested}`?"))
+            }
+            None => err,
+        }
+    }
+
+    /// Read struct fields at `path` that are not set from the files named by
+    /// their `<NAME>_FILE` env, see [`EnvDeserializer::file_vars`].
+    ///
+    /// The `_FILE` env is taken from the node either way, so that it's not
+    /// seen as a nested key of the field.
+    fn read_file_vars(
+        &self,
+        node: &mut Node,
+        state: &State,
+        path: &str,
+        fields: &[&str],
+    ) -> Result<(), Error> {
+        for field in fields {
+            let field = self.field_key(field);
+            let key = if self.case_sensitive && !field.contains(char::is_lowercase) {
+                format!("{field}_FILE")
+            } else {
+                format!("{field}_file")
             };
+            if fields.iter().any(|f| self.field_key(f) == key) {
+                continue;
+            }
 
-            match self.node.get(&key) {
-                // If key is not found inside node, skip it and continue.
+            let (field_path, file_path) =
+                (self.join_path(path, &field), self.join_path(path, &key));
+            // Env that failed in previous attempts is treated as unset.
+            if state.is_failed(self, &field_path) || state.is_failed(self, &file_path) {
+                node.take_value(&key, &self.separator);
+                continue;
+            }
+            let file = match node.take_value(&key, &self.separator) {
                 None => continue,
-                Some(v) => {
-                    self.last_value = Some(v.clone());
-                    return Ok(Some(seed.deserialize(KeyDeserializer::new(key))?));
-                }
+                Some(Value::Str(v)) => PathBuf::from(v),
+                Some(Value::Os(_, v)) => PathBuf::from(v),
+            };
+            if node
+                .get(&field, &self.separator)
+                .is_some_and(|n| n.raw_value().is_some())
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidValue,
+                    format_args!(
+                        "`{}` is set as well, only one of them is allowed",
+                        self.env_name(&file_path)
+                    ),
+                )
+                .with_env(self.env_name(&field_path), None));
+            }
+
+            let content = fs::read_to_string(&file)
+                .map_err(|err| Error::io(&file, err).with_env(self.env_name(&file_path), None))?;
+            let content = content.strip_suffix('\n').unwrap_or(&content);
+            let content = content.strip_suffix('\r').unwrap_or(content);
+            node.push(&field, content, &self.separator);
+            state.mark_used(self, &file_path);
+            state
+                .file_vars
+                .borrow_mut()
+                .insert(self.env_name(&field_path));
+        }
+        Ok(())
+    }
+
+    /// Deserialize the tree again and again, treating env that failed as
+    /// unset, until it succeeds or no more progress can be made.
+    fn deserialize_collecting<T>(&self, node: Node, state: &State, path: String) -> Result<T, Error>
+    where
+        T: de::DeserializeOwned,
+    {
+        let mut errors = Vec::new();
+        loop {
+            let progress = state.failed.borrow().len();
+            let err =
+                match T::deserialize(Deserializer::new(node.clone(), self, state, path.clone())) {
+                    Ok(v) if errors.is_empty() => return Ok(v),
+                    Ok(_) => return Err(Error::multiple(errors)),
+                    Err(err) => err,
+                };
+
+            // Errors of env that failed before are caused by treating it as
+            // unset, they are reported already.
+            let new = match err.key() {
+                Some(k) => state.failed.borrow_mut().insert(k.to_string()),
+                None => true,
+            };
+            if new {
+                errors.push(err);
+            }
+            if state.failed.borrow().len() == progress {
+                return Err(Error::multiple(errors));
             }
         }
     }
 
-    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    /// Build the tree of env kept under the prefix.
+    fn node<Iter, S>(&self, iter: Iter) -> Node
     where
-        V: DeserializeSeed<'de>,
+        Iter: IntoIterator<Item = (S, Value)>,
+        S: AsRef<str>,
     {
-        let value = self
-            .last_value
-            .take()
-            .expect("value for current entry is missing");
+        let filter = |_: &str, v: &Value| self.keep_env(v);
+        match &self.prefix {
+            None => Node::from_iter(iter, &self.separator, self.case_sensitive, filter),
+            Some(prefix) => Node::from_iter_with_prefix(
+                iter,
+                prefix,
+                &self.separator,
+                self.case_sensitive,
+                filter,
+            ),
+        }
+    }
+
+    fn deserialize<Iter, S, T>(&self, iter: Iter) -> Result<T, Error>
+    where
+        Iter: IntoIterator<Item = (S, Value)>,
+        S: AsRef<str>,
+        T: de::DeserializeOwned,
+    {
+        self.deserialize_with_unused(iter).map(|(v, _)| v)
+    }
+
+    /// Deserialize into struct, and return the names of env in the tree that
+    /// no field reads.
+    fn deserialize_with_unused<Iter, S, T>(&self, iter: Iter) -> Result<(T, Vec<String>), Error>
+    where
+        Iter: IntoIterator<Item = (S, Value)>,
+        S: AsRef<str>,
+        T: de::DeserializeOwned,
+    {
+        if self.separator.is_empty() {
+            return Err(de::Error::custom("separator must not be empty"));
+        }
+        if self.list_delimiters.is_empty() || self.list_delimiters.iter().any(|d| d.is_empty()) {
+            return Err(de::Error::custom("list delimiter must not be empty"));
+        }
+
+        let (node, expander) = if self.interpolate {
+            let vars: Vec<_> = iter
+                .into_iter()
+                .map(|(k, v)| (k.as_ref().to_string(), v))
+                .collect();
+            let node = self.node(vars.iter().map(|(k, v)| (k, v.clone())));
+            (node, Some(RefCell::new(Expander::new(vars))))
+        } else {
+            (self.node(iter), None)
+        };
+        let matched = !node.is_empty();
+
+        let path = self.prefix.clone().unwrap_or_default();
+        let names = node
+            .flatten("", &self.separator)
+            .into_iter()
+            .filter(|k| {
+                node.get(k, &self.separator)
+                    .is_some_and(|n| n.raw_value().is_some())
+            })
+            .map(|k| self.env_name(&self.join_path(&path, &k)))
+            .collect();
+        let state = State {
+            names,
+            expander,
+            ..State::default()
+        };
+        let result = if self.collect_errors {
+            self.deserialize_collecting(node, &state, path)
+        } else {
+            T::deserialize(Deserializer::new(node, self, &state, path))
+        };
+        let value = match (result, &self.prefix) {
+            // Most likely the prefix is wrong, tell users about it.
+            (Err(err), Some(prefix)) if !matched => {
+                return Err(err.with_hint(format_args!(
+                    "no env found with prefix `{prefix}{}`",
+                    self.separator
+                )))
+            }
+            // A wrong prefix goes unnoticed if all fields have defaults.
+            (Ok(_), Some(prefix)) if !matched && self.deny_unused => {
+                return Err(Error::new(
+                    ErrorKind::Custom,
+                    format_args!("no env found with prefix `{prefix}{}`", self.separator),
+                ))
+            }
+            (result, _) => result?,
+        };
+
+        let used = state.used.into_inner();
+        let buffered = state.buffered.into_inner();
+        let unused: Vec<_> = state
+            .names
+            .into_iter()
+            .filter(|k| !used.contains(k))
+            .collect();
+        if self.deny_unused && !(unused.is_empty() && buffered.is_empty()) {
+            let mut errors: Vec<_> = unused
+                .into_iter()
+                .map(|k| (k, "not used by any field"))
+                .chain(buffered.into_iter().map(|k| {
+                    (
+                        k,
+                        "read for `#[serde(flatten)]` fields, can't tell if any uses it",
+                    )
+                }))
+                .collect();
+            errors.sort();
+            return Err(Error::multiple(
+                errors
+                    .into_iter()
+                    .map(|(k, msg)| Error::new(ErrorKind::UnknownField, msg).with_env(k, None))
+                    .collect(),
+            ));
+        }
+        Ok((value, unused))
+    }
+}
+
+/// State shared by a whole deserialization.
+#[derive(Default)]
+struct State {
+    /// Env names that failed in previous attempts under
+    /// [`EnvDeserializer::collect_errors`], which are treated as unset.
+    failed: RefCell<BTreeSet<String>>,
+    /// Names of env that are set in the tree.
+    names: BTreeSet<String>,
+    /// Env names whose values are read from files, which are secret.
+    file_vars: RefCell<BTreeSet<String>>,
+    /// Env names whose values have been read.
+    used: RefCell<BTreeSet<String>>,
+    /// Env names whose values serde has buffered for the `#[serde(flatten)]`
+    /// fields of a struct, which may or may not read them.
+    buffered: RefCell<BTreeSet<String>>,
+    /// Expander of references in values under
+    /// [`EnvDeserializer::interpolate`].
+    expander: Option<RefCell<Expander>>,
+}
 
-        seed.deserialize(Deserializer(value))
+impl State {
+    fn is_failed(&self, cfg: &EnvDeserializer, path: &str) -> bool {
+        let failed = self.failed.borrow();
+        !failed.is_empty() && failed.contains(&cfg.env_name(path))
+    }
+
+    fn mark_used(&self, cfg: &EnvDeserializer, path: &str) {
+        self.used.borrow_mut().insert(cfg.env_name(path));
     }
 }
 
-struct EnumAccessor {
-    keys: std::vec::IntoIter<String>,
+/// Deserializer for a [`Node`], carrying the options it was built with.
+struct Deserializer<'a> {
     node: Node,
+    cfg: &'a EnvDeserializer,
+    state: &'a State,
+    /// Nesting level of the list this node is split from, `0` for nodes
+    /// that are not list elements.
+    level: usize,
+    /// Keys from the root to this node joined by the separator, starting
+    /// with the prefix, used to name the env in errors.
+    path: String,
+    /// Whether values visited by `deserialize_any` are inferred as booleans
+    /// or numbers, see [`MapAccessor::infer`].
+    infer: bool,
+    /// Whether references in the value are expanded already, so they are
+    /// not expanded twice.
+    expanded: bool,
 }
 
-impl EnumAccessor {
-    fn new(keys: Vec<String>, node: Node) -> Self {
+impl<'a> Deserializer<'a> {
+    fn new(node: Node, cfg: &'a EnvDeserializer, state: &'a State, path: String) -> Self {
         Self {
-            keys: keys.into_iter(),
             node,
+            cfg,
+            state,
+            level: 0,
+            path,
+            infer: false,
+            expanded: false,
         }
     }
-}
 
-impl<'de> de::EnumAccess<'de> for EnumAccessor {
-    type Error = Error;
-    type Variant = VariantAccessor;
+    /// Name the env and value of this node in the error if it doesn't name
+    /// one yet.
+    fn env_error(&self, err: Error) -> Error {
+        if self.path.is_empty() {
+            return err;
+        }
+        let value = self.node.raw_value().and_then(Value::as_str);
+        self.cfg.env_error(self.state, err, &self.path, value)
+    }
+
+    /// Expand references in the value under [`EnvDeserializer::interpolate`],
+    /// which unsets it if it expands to an empty or null value that's not
+    /// kept.
+    fn expand(&mut self) -> Result<(), Error> {
+        let Some(expander) = &self.state.expander else {
+            return Ok(());
+        };
+        if self.expanded {
+            return Ok(());
+        }
+        self.expanded = true;
+        let Some(Value::Str(v)) = self.node.raw_value() else {
+            return Ok(());
+        };
+        let v = Value::from(
+            expander
+                .borrow_mut()
+                .expand(&self.cfg.env_name(&self.path), v)?,
+        );
+        let keep = self.cfg.keep_env(&v);
+        self.node.set_value(keep.then_some(v));
+        Ok(())
+    }
+
+    /// Record that the value of this node is read, if it's set, and reject
+    /// an empty value under [`EmptyValues::Error`].
+    fn mark_used(&mut self) -> Result<(), Error> {
+        self.expand()?;
+        let Some(v) = self.node.raw_value() else {
+            return Ok(());
+        };
+        if !self.path.is_empty() {
+            self.state.mark_used(self.cfg, &self.path);
+        }
+        if v.is_empty() && self.cfg.empty_values == EmptyValues::Error {
+            let err = Error::new(ErrorKind::InvalidValue, "value must not be empty");
+            return Err(self.env_error(err));
+        }
+        Ok(())
+    }
 
-    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    /// Parse value into `T`, report an error with the value and the expected
+    /// type.
+    fn parse<T>(&mut self) -> Result<T, Error>
     where
-        V: DeserializeSeed<'de>,
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
     {
-        let key = self
-            .keys
-            .find(|key| self.node.value() == key)
-            .ok_or_else(|| de::Error::custom("no variant found"))?;
+        self.mark_used()?;
+        let v = self.str_value()?;
+        v.parse().map_err(|err| {
+            let secret = !self.path.is_empty()
+                && self
+                    .cfg
+                    .is_secret(self.state, &self.cfg.env_name(&self.path));
+            self.env_error(Error::parse(v, type_name::<T>(), err, secret))
+        })
+    }
 
-        let variant = VariantAccessor::new(self.node);
-        Ok((seed.deserialize(key.into_deserializer())?, variant))
+    /// Get value as str, report an error naming the env if it's not valid
+    /// unicode.
+    fn str_value(&self) -> Result<&str, Error> {
+        match self.node.raw_value() {
+            Some(Value::Os(k, _)) => Err(Error::new(
+                ErrorKind::InvalidValue,
+                "value is not valid unicode",
+            )
+            .with_env(k.clone(), None)),
+            _ => Ok(self.node.value()),
+        }
     }
-}
 
-struct VariantAccessor {
-    node: Node,
+    /// Split value into list elements.
+    ///
+    /// `keep_empty` is used if the policy is not set explicitly.
+    fn split_list(&mut self, keep_empty: bool) -> Result<Vec<Node>, Error> {
+        self.mark_used()?;
+        let delimiter = self.cfg.list_delimiters.get(self.level).ok_or_else(|| {
+            de::Error::custom(format!(
+                "no list delimiter configured for nesting level {}",
+                self.level
+            ))
+        })?;
+
+        let elements = list::split(
+            self.str_value()?,
+            delimiter,
+            self.cfg.list_escape,
+            self.cfg.trim_list_elements,
+            self.cfg.keep_empty_list_elements.unwrap_or(keep_empty),
+        )?;
+        Ok(elements
+            .into_iter()
+            .map(|v| {
+                // Empty elements are unset unless empty values are kept.
+                if v.is_empty() && self.cfg.empty_values != EmptyValues::Empty {
+                    Node::default()
+                } else {
+                    Node::new(v)
+                }
+            })
+            .collect())
+    }
+
+    /// Collect indexed children like `SERVERS_0_HOST`, `SERVERS_1_HOST` as
+    /// sequence elements.
+    ///
+    /// Indexes must start from `0` without gaps.
+    fn indexed_elements(self) -> Result<Vec<Node>, Error> {
+        let children = self.node.into_indexed_children();
+
+        let mut elements = Vec::with_capacity(children.len());
+        for (expected, (idx, node)) in children.into_iter().enumerate() {
+            if idx != expected {
+                return Err(de::Error::custom(format!(
+                    "missing index {expected} in sequence, found index {idx}"
+                )));
+            }
+            elements.push(node);
+        }
+        Ok(elements)
+    }
 }
 
-impl VariantAccessor {
-    fn new(node: Node) -> Self {
-        Self { node }
+/// Parse value as a finite float, rejecting words like `inf` and `NaN`.
+fn parse_float(v: &str) -> Option<f64> {
+    if !v.contains(|c: char| c.is_ascii_digit()) {
+        return None;
     }
+    v.parse::<f64>().ok().filter(|v| v.is_finite())
 }
 
-impl<'de> de::VariantAccess<'de> for VariantAccessor {
+impl<'de> de::Deserializer<'de> for Deserializer<'_> {
     type Error = Error;
 
-    fn unit_variant(self) -> Result<(), Self::Error> {
-        if self.node.has_children() {
-            return Err(de::Error::custom("variant is not unit"));
+    fn deserialize_any<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // Subtrees are visited as maps keyed by their children, the value of
+        // the node itself is not visited then.
+        let keys = self.node.child_keys();
+        if !keys.is_empty() {
+            let (cfg, state, path) = (self.cfg, self.state, self.path.clone());
+            let map = MapAccessor {
+                infer: self.infer,
+                ..MapAccessor::new_dynamic(keys, self.node, cfg, state, self.path)
+            };
+            return vis
+                .visit_map(map)
+                .map_err(|err| cfg.field_error(state, &path, err));
         }
-        Ok(())
+
+        self.mark_used()?;
+        let v = self.str_value()?;
+        let result = if !self.cfg.infer_types && !self.infer {
+            vis.visit_str(v)
+        } else if let Ok(v) = v.parse::<bool>() {
+            vis.visit_bool(v)
+        } else if let Ok(v) = v.parse::<u64>() {
+            vis.visit_u64(v)
+        } else if let Ok(v) = v.parse::<i64>() {
+            vis.visit_i64(v)
+        } else if let Some(v) = parse_float(v) {
+            vis.visit_f64(v)
+        } else {
+            vis.visit_str(v)
+        };
+        result.map_err(|err| self.env_error(err))
     }
-    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+
+    fn deserialize_bool<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
     where
-        T: DeserializeSeed<'de>,
+        V: Visitor<'de>,
     {
-        seed.deserialize(Deserializer(self.node))
+        vis.visit_bool(self.parse()?)
     }
-    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
+
+    fn deserialize_i8<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(de::Error::custom("tuple variant is not supported"))
+        vis.visit_i8(self.parse()?)
     }
-    fn struct_variant<V>(
-        self,
-        fields: &'static [&'static str],
-        visitor: V,
-    ) -> Result<V::Value, Self::Error>
+
+    fn deserialize_i16<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        let keys = fields.iter().map(|v| v.to_string()).collect();
+        vis.visit_i16(self.parse()?)
+    }
 
-        visitor.visit_map(MapAccessor::new(keys, self.node))
+    fn deserialize_i32<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_i32(self.parse()?)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use serde::Deserialize;
-    use std::collections::HashMap;
+    fn deserialize_i64<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_i64(self.parse()?)
+    }
 
-    use super::*;
+    fn deserialize_u8<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_u8(self.parse()?)
+    }
 
-    #[derive(Deserialize, Default, PartialEq, Debug)]
-    #[serde(default)]
-    struct TestStruct {
-        a: i64,
-        b: bool,
-        c: String,
-        d: EmbedStruct,
+    fn deserialize_u16<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_u16(self.parse()?)
     }
 
-    #[derive(Deserialize, Default, PartialEq, Debug)]
-    #[serde(default)]
-    struct EmbedStruct {
-        aa: f32,
-        bb: String,
+    forward_to_deserialize_any! {
+        unit unit_struct
+        ignored_any
     }
 
-    #[test]
-    fn test_from_env() {
-        temp_env::with_vars(
-            vec![
-                ("A", Some("123")),
-                ("B", Some("true")),
-                ("C", Some("Hello, test")),
-                ("D_AA", Some("1.2")),
-                ("D_BB", Some("Hello, embed")),
-            ],
-            || {
-                let t: TestStruct = from_env().expect("must success");
-                assert_eq!(
-                    t,
-                    TestStruct {
-                        a: 123,
-                        b: true,
-                        c: "Hello, test".to_string(),
-                        d: EmbedStruct {
-                            aa: 1.2,
-                            bb: "Hello, embed".to_string()
-                        }
-                    }
-                )
-            },
-        )
+    fn deserialize_u32<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_u32(self.parse()?)
     }
 
-    /// This test is ported from [softprops/envy](https://github.com/softprops/envy/blob/801d81e7c3e443470e110bf4e34460acba113476/src/lib.rs#L410)
-    #[derive(Deserialize, Debug, PartialEq, Eq)]
-    struct Foo {
-        bar: String,
-        baz: bool,
-        zoom: Option<u16>,
-        doom: Vec<u64>,
-        boom: Vec<String>,
-        #[serde(default = "default_kaboom")]
-        kaboom: u16,
-        #[serde(default)]
-        debug_mode: bool,
-        provided: Option<String>,
-        newtype: CustomNewType,
-        boom_zoom: bool,
-        #[serde(default = "default_bool")]
-        mode_xx: bool,
+    fn deserialize_u64<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_u64(self.parse()?)
     }
 
-    fn default_bool() -> bool {
-        true
+    fn deserialize_f32<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_f32(self.parse()?)
     }
 
-    fn default_kaboom() -> u16 {
-        8080
+    fn deserialize_f64<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_f64(self.parse()?)
     }
 
-    #[derive(Deserialize, Debug, PartialEq, Eq, Default)]
-    struct CustomNewType(u32);
+    fn deserialize_char<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_char(self.parse()?)
+    }
 
-    #[test]
-    fn test_ported_from_envy() {
-        temp_env::with_vars(
-            vec![
-                ("BAR", Some("test")),
-                ("BAZ", Some("true")),
-                ("DOOM", Some("1, 2, 3 ")),
-                // Empty string should result in empty vector.
-                ("BOOM", Some("")),
-                ("SIZE", Some("small")),
-                ("PROVIDED", Some("test")),
-                ("NEWTYPE", Some("42")),
-                ("boom_zoom", Some("true")),
-                ("mode_xx", Some("false")),
-            ],
-            || {
-                let actual: Foo = from_env().expect("must success");
-                assert_eq!(
-                    actual,
-                    Foo {
-                        bar: String::from("test"),
-                        baz: true,
-                        zoom: None,
-                        doom: vec![1, 2, 3],
-                        boom: vec![],
-                        kaboom: 8080,
-                        debug_mode: false,
+    fn deserialize_str<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.mark_used()?;
+        vis.visit_str(self.str_value()?)
+            .map_err(|err| self.env_error(err))
+    }
+
+    fn deserialize_string<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.mark_used()?;
+        self.str_value()?;
+        vis.visit_string(self.node.into_value())
+    }
+
+    fn deserialize_bytes<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.mark_used()?;
+        let bytes = self.node.into_raw_value().map(Value::into_bytes);
+        vis.visit_bytes(&bytes.unwrap_or_default())
+    }
+
+    fn deserialize_byte_buf<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.mark_used()?;
+        let bytes = self.node.into_raw_value().map(Value::into_bytes);
+        vis.visit_byte_buf(bytes.unwrap_or_default())
+    }
+
+    fn deserialize_option<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // A value that expands to empty is unset.
+        self.expand()?;
+        if self.node.is_empty() {
+            vis.visit_none()
+        } else {
+            vis.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        vis: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.node.has_indexed_children() {
+            let (cfg, state, path) = (self.cfg, self.state, self.path.clone());
+            // Indexed elements are separate env, so they start from level 0 again.
+            return vis.visit_seq(SeqAccessor::new_indexed(
+                self.indexed_elements()?,
+                cfg,
+                state,
+                path,
+            ));
+        }
+
+        // Value that is not valid unicode can only be raw bytes like `Vec<u8>`.
+        if let Some(Value::Os(..)) = self.node.raw_value() {
+            self.mark_used()?;
+            let bytes = self.node.into_raw_value().map(Value::into_bytes);
+            return vis.visit_seq(SeqDeserializer::new(bytes.unwrap_or_default().into_iter()));
+        }
+
+        self.mark_used()?;
+        let elements = if self.node.value().is_empty() {
+            Vec::new()
+        } else {
+            self.split_list(false)?
+        };
+
+        vis.visit_seq(SeqAccessor::new(
+            elements,
+            self.cfg,
+            self.state,
+            self.level + 1,
+            self.path,
+        ))
+    }
+
+    fn deserialize_tuple<V>(mut self, _len: usize, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.node.has_indexed_children() {
+            let (cfg, state, path) = (self.cfg, self.state, self.path.clone());
+            // Indexed elements are separate env, so they start from level 0 again.
+            return vis.visit_seq(SeqAccessor::new_indexed(
+                self.indexed_elements()?,
+                cfg,
+                state,
+                path,
+            ));
+        }
+
+        let elements = self.split_list(true)?;
+
+        vis.visit_seq(SeqAccessor::new(
+            elements,
+            self.cfg,
+            self.state,
+            self.level + 1,
+            self.path,
+        ))
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        vis: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, vis)
+    }
+
+    fn deserialize_map<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let keys = self.node.flatten("", &self.cfg.separator);
+        let (cfg, state, path) = (self.cfg, self.state, self.path.clone());
+        let node = self.node.clone();
+        let map = MapAccessor {
+            infer: self.infer,
+            ..MapAccessor::new_dynamic(keys, self.node, cfg, state, self.path)
+        };
+        vis.visit_map(map).map_err(|err| {
+            let err = cfg.buffered_error(state, &node, &path, err);
+            cfg.field_error(state, &path, err)
+        })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        vis: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let keys = fields.iter().map(|v| v.to_string()).collect();
+
+        let (cfg, state, path) = (self.cfg, self.state, self.path.clone());
+        let mut node = self.node;
+        if cfg.file_vars {
+            cfg.read_file_vars(&mut node, state, &path, fields)?;
+        }
+        vis.visit_map(MapAccessor::new(keys, node, cfg, state, self.path))
+            .map_err(|err| cfg.field_error(state, &path, err))
+    }
+
+    fn deserialize_identifier<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(vis)
+    }
+
+    fn deserialize_enum<V>(
+        mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        vis: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.mark_used()?;
+        // `OsString` is deserialized as an enum of its platform encoding.
+        #[cfg(any(unix, windows))]
+        if name == "OsString" {
+            return vis.visit_enum(OsStringAccessor(self.node.into_raw_value()));
+        }
+        #[cfg(not(any(unix, windows)))]
+        let _ = name;
+
+        vis.visit_enum(EnumAccessor::new(
+            variants, self.node, self.cfg, self.state, self.path,
+        ))
+    }
+}
+
+/// OsStringAccessor is used to deserialize value into `OsString`.
+///
+/// serde represents `OsString` as a `Unix` variant of bytes or a `Windows`
+/// variant of wide chars, so the raw value is kept losslessly.
+#[cfg(any(unix, windows))]
+struct OsStringAccessor(Option<Value>);
+
+#[cfg(any(unix, windows))]
+impl<'de> de::EnumAccess<'de> for OsStringAccessor {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = if cfg!(unix) { "Unix" } else { "Windows" };
+        let variant = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(variant))?;
+        Ok((variant, self))
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl<'de> de::VariantAccess<'de> for OsStringAccessor {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Err(de::Error::custom("OsString is not unit"))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        #[cfg(unix)]
+        let raw = self.0.map(Value::into_bytes);
+        #[cfg(windows)]
+        let raw = self.0.map(Value::into_wide);
+
+        seed.deserialize(SeqDeserializer::new(raw.unwrap_or_default().into_iter()))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::custom("OsString is not tuple"))
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::custom("OsString is not struct"))
+    }
+}
+
+/// KeyDeserializer is used to deserialize key of a map/enum/struct.
+///
+/// It's similar to `StringDeserializer`, but only used for key deserialization in serde-env where:
+///
+/// - Key is case insensitive: `PATH`, `Path`, `path`, `PatH` all map to the same key,
+///   unless `case_sensitive` is set.
+/// - Key is prefix based: Key could be adapted based on the deserialize target itself.
+struct KeyDeserializer<'a> {
+    key: String,
+    case_sensitive: bool,
+    /// Set if the key is read as a struct field, see [`MapAccessor::fields`].
+    read_as_field: Option<&'a Cell<bool>>,
+    /// Key used instead if the key is read as a struct field.
+    field_key: Option<String>,
+}
+
+impl KeyDeserializer<'_> {
+    fn new(key: String, case_sensitive: bool) -> Self {
+        Self {
+            key,
+            case_sensitive,
+            read_as_field: None,
+            field_key: None,
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_str(&self.key)
+    }
+
+    fn deserialize_str<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_str(&self.key)
+    }
+
+    fn deserialize_string<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_string(self.key)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        for v in variants {
+            // Return the enum variant if it matches the key.
+            let matched = if self.case_sensitive {
+                self.key == *v
+            } else {
+                self.key.eq_ignore_ascii_case(v)
+            };
+            if matched {
+                return visitor.visit_enum(v.into_deserializer());
+            }
+        }
+        Err(de::Error::unknown_variant(&self.key, variants))
+    }
+
+    fn deserialize_identifier<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if let Some(read_as_field) = self.read_as_field {
+            read_as_field.set(true);
+        }
+        vis.visit_str(self.field_key.as_ref().unwrap_or(&self.key))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct ignored_any
+    }
+}
+
+struct SeqAccessor<'a> {
+    elements: std::iter::Enumerate<std::vec::IntoIter<Node>>,
+    cfg: &'a EnvDeserializer,
+    state: &'a State,
+    /// Nesting level of the elements.
+    level: usize,
+    /// Path of the sequence.
+    path: String,
+    /// Whether elements are indexed env like `SERVERS_0` instead of split
+    /// from the value of the sequence.
+    indexed: bool,
+}
+
+impl<'a> SeqAccessor<'a> {
+    fn new(
+        keys: Vec<Node>,
+        cfg: &'a EnvDeserializer,
+        state: &'a State,
+        level: usize,
+        path: String,
+    ) -> Self {
+        Self {
+            elements: keys.into_iter().enumerate(),
+            cfg,
+            state,
+            level,
+            path,
+            indexed: false,
+        }
+    }
+
+    fn new_indexed(
+        keys: Vec<Node>,
+        cfg: &'a EnvDeserializer,
+        state: &'a State,
+        path: String,
+    ) -> Self {
+        Self {
+            indexed: true,
+            ..Self::new(keys, cfg, state, 0, path)
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for SeqAccessor<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.elements.next() {
+            None => Ok(None),
+            Some((idx, v)) => {
+                let path = if self.indexed {
+                    self.cfg.join_path(&self.path, &idx.to_string())
+                } else {
+                    self.path.clone()
+                };
+                let raw = self.cfg.secret_value(self.state, &path, &v);
+                let de = Deserializer {
+                    node: v,
+                    cfg: self.cfg,
+                    state: self.state,
+                    level: self.level,
+                    path: path.clone(),
+                    infer: false,
+                    // Elements split from a value are expanded with it.
+                    expanded: !self.indexed,
+                };
+                let value = seed
+                    .deserialize(de)
+                    .map_err(|err| self.cfg.env_error(self.state, err, &path, raw.as_deref()))?;
+                Ok(Some(value))
+            }
+        }
+    }
+}
+
+struct MapAccessor<'a> {
+    /// Path and node of the value for the last key.
+    last_value: Option<(String, Node)>,
+    keys: std::collections::btree_set::IntoIter<String>,
+    node: Node,
+    cfg: &'a EnvDeserializer,
+    state: &'a State,
+    /// Whether keys are dynamic (map) instead of fields (struct).
+    ///
+    /// For dynamic keys, a value deserialized as struct or map consumes the
+    /// whole subtree under its key, so the nested keys are skipped.
+    dynamic: bool,
+    last_key: Option<String>,
+    last_nested: Cell<bool>,
+    nested_keys: Vec<String>,
+    /// Dynamic keys of env that have been read in place of a node without
+    /// value.
+    read_keys: Vec<String>,
+    /// Whether dynamic keys are read as struct fields, `None` until the first
+    /// key is read.
+    ///
+    /// serde reads structs with `#[serde(flatten)]` fields as maps, and
+    /// buffers the entries they don't declare for the flattened fields to
+    /// take by name. So such structs are presented env by the full key
+    /// after the map, like `max_conns` for `APP_MAX_CONNS`, with the value
+    /// only, as if the flattened fields were declared inline.
+    fields: Option<bool>,
+    /// Whether values visited by `deserialize_any` are inferred as booleans
+    /// or numbers, inherited from the node of the map.
+    infer: bool,
+    /// Path of the map.
+    path: String,
+}
+
+impl<'a> MapAccessor<'a> {
+    fn new(
+        keys: BTreeSet<String>,
+        node: Node,
+        cfg: &'a EnvDeserializer,
+        state: &'a State,
+        path: String,
+    ) -> Self {
+        Self {
+            last_value: None,
+            keys: keys.into_iter(),
+            node,
+            cfg,
+            state,
+            path,
+            dynamic: false,
+            last_key: None,
+            last_nested: Cell::new(false),
+            nested_keys: Vec::new(),
+            read_keys: Vec::new(),
+            fields: None,
+            infer: false,
+        }
+    }
+
+    fn new_dynamic(
+        keys: BTreeSet<String>,
+        node: Node,
+        cfg: &'a EnvDeserializer,
+        state: &'a State,
+        path: String,
+    ) -> Self {
+        Self {
+            dynamic: true,
+            ..Self::new(keys, node, cfg, state, path)
+        }
+    }
+
+    /// Check if key is inside a subtree that has been consumed already, or
+    /// has been read in place of a node without value.
+    fn is_consumed(&self, key: &str) -> bool {
+        self.read_keys.iter().any(|k| k == key)
+            || self.nested_keys.iter().any(|k| {
+                key.strip_prefix(k.as_str())
+                    .is_some_and(|remain| remain.starts_with(&self.cfg.separator))
+            })
+    }
+
+    /// Find the next key to visit, with the path and node of its value.
+    fn next_entry(&mut self) -> Option<(String, String, Node)> {
+        if let Some(key) = self.last_key.take() {
+            if self.last_nested.replace(false) {
+                self.nested_keys.push(key);
+            }
+        }
+
+        loop {
+            let key = self.keys.next()?;
+
+            if self.is_consumed(&key) {
+                continue;
+            }
+
+            // Fields are looked up by their key in the tree, while dynamic
+            // keys come from the tree already.
+            let lookup = if self.dynamic {
+                key.clone()
+            } else {
+                self.cfg.field_key(&key)
+            };
+            let path = self.cfg.join_path(&self.path, &lookup);
+            match self.node.get(&lookup, &self.cfg.separator) {
+                // If key is not found inside node, skip it and continue.
+                None => continue,
+                Some(v) => {
+                    // Env that failed in previous attempts is treated as unset.
+                    if self.state.is_failed(self.cfg, &path) {
+                        if self.dynamic {
+                            self.nested_keys.push(key);
+                        }
+                        continue;
+                    }
+                    if self.fields == Some(true) {
+                        let Some(value) = v.raw_value() else {
+                            continue;
+                        };
+                        return Some((key, path, Node::new(value.clone())));
+                    }
+                    if self.dynamic {
+                        self.last_key = Some(key.clone());
+                    }
+                    return Some((key, path, v.clone()));
+                }
+            }
+        }
+    }
+
+    /// Find the first env with value under the node of a dynamic key, with
+    /// its key and path.
+    fn first_leaf(&self, key: &str, node: &Node) -> Option<(String, String, Node)> {
+        let sep = &self.cfg.separator;
+        node.flatten("", sep).into_iter().find_map(|k| {
+            let leaf = node.get(&k, sep).filter(|n| n.raw_value().is_some())?;
+            let key = self.cfg.join_path(key, &k);
+            let path = self.cfg.join_path(&self.path, &key);
+            Some((key, path, leaf.clone()))
+        })
+    }
+
+    fn value_deserializer(&self, node: Node, path: String) -> Deserializer<'a> {
+        Deserializer {
+            infer: self.infer,
+            ..Deserializer::new(node, self.cfg, self.state, path)
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccessor<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        debug_assert!(
+            self.last_value.is_none(),
+            "value for the last entry is not deserialized"
+        );
+
+        let Some((key, path, node)) = self.next_entry() else {
+            return Ok(None);
+        };
+        if !self.dynamic || self.fields.is_some() {
+            let key = seed
+                .deserialize(KeyDeserializer::new(key, self.cfg.case_sensitive))
+                .map_err(|err| self.cfg.env_error(self.state, err, &path, None))?;
+            self.last_value = Some((path, node));
+            return Ok(Some(key));
+        }
+
+        // The first dynamic key tells if the map is a struct, which reads its
+        // first env by the full key.
+        let leaf = match node.raw_value() {
+            None => self.first_leaf(&key, &node),
+            Some(_) => None,
+        };
+        let read_as_field = Cell::new(false);
+        let result = seed.deserialize(KeyDeserializer {
+            read_as_field: Some(&read_as_field),
+            field_key: leaf.as_ref().map(|(key, ..)| key.clone()),
+            ..KeyDeserializer::new(key, self.cfg.case_sensitive)
+        });
+        self.fields = Some(read_as_field.get());
+        let (path, node) = match leaf {
+            Some((key, path, leaf)) if read_as_field.get() => {
+                self.read_keys.push(key);
+                (path, leaf)
+            }
+            _ if read_as_field.get() => {
+                let mut value = Node::default();
+                value.set_value(node.into_raw_value());
+                (path, value)
+            }
+            _ => (path, node),
+        };
+        let key = result.map_err(|err| self.cfg.env_error(self.state, err, &path, None))?;
+        self.last_value = Some((path, node));
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (path, value) = self
+            .last_value
+            .take()
+            .expect("value for current entry is missing");
+
+        let raw = self.cfg.secret_value(self.state, &path, &value);
+        let de = self.value_deserializer(value, path.clone());
+        let result = if self.dynamic {
+            seed.deserialize(MapValueDeserializer {
+                de,
+                nested: &self.last_nested,
+                leaf: None,
+                buffered: self.fields == Some(true),
+            })
+        } else {
+            seed.deserialize(de)
+        };
+        result.map_err(|err| self.cfg.env_error(self.state, err, &path, raw.as_deref()))
+    }
+
+    fn next_entry_seed<K, V>(
+        &mut self,
+        kseed: K,
+        vseed: V,
+    ) -> Result<Option<(K::Value, V::Value)>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+        V: DeserializeSeed<'de>,
+    {
+        if !self.dynamic {
+            return match self.next_key_seed(kseed)? {
+                Some(key) => Ok(Some((key, self.next_value_seed(vseed)?))),
+                None => Ok(None),
+            };
+        }
+
+        // The value of a dynamic key is read first: a node without value is
+        // only an entry if it's read as a struct or map, otherwise the first
+        // env under it is.
+        let Some((key, path, node)) = self.next_entry() else {
+            return Ok(None);
+        };
+        let leaf = match node.raw_value() {
+            None => self.first_leaf(&key, &node),
+            Some(_) => None,
+        };
+        let read_leaf = Cell::new(false);
+        let result = vseed.deserialize(MapValueDeserializer {
+            de: self.value_deserializer(node.clone(), path.clone()),
+            nested: &self.last_nested,
+            leaf: leaf.as_ref().map(|(_, path, node)| {
+                (
+                    self.value_deserializer(node.clone(), path.clone()),
+                    &read_leaf,
+                )
+            }),
+            buffered: false,
+        });
+        let (key, path, node) = match leaf {
+            Some(leaf) if read_leaf.get() => {
+                self.read_keys.push(leaf.0.clone());
+                leaf
+            }
+            _ => (key, path, node),
+        };
+
+        let raw = self.cfg.secret_value(self.state, &path, &node);
+        let value =
+            result.map_err(|err| self.cfg.env_error(self.state, err, &path, raw.as_deref()))?;
+        let key = kseed
+            .deserialize(KeyDeserializer::new(key, self.cfg.case_sensitive))
+            .map_err(|err| self.cfg.env_error(self.state, err, &path, None))?;
+        Ok(Some((key, value)))
+    }
+}
+
+/// MapValueDeserializer is used to deserialize value of a map.
+///
+/// It records whether the value is deserialized as struct or map, so that
+/// [`MapAccessor`] knows the whole subtree has been consumed.
+struct MapValueDeserializer<'a, 'b> {
+    de: Deserializer<'a>,
+    nested: &'b Cell<bool>,
+    /// For a node without value, the first env under it which is read
+    /// instead if the value is not nested, and whether it has been read.
+    leaf: Option<(Deserializer<'a>, &'b Cell<bool>)>,
+    /// Whether the value is of a struct with `#[serde(flatten)]` fields, where
+    /// serde buffers the values it reads without known types, see
+    /// [`MapAccessor::fields`].
+    buffered: bool,
+}
+
+impl<'a> MapValueDeserializer<'a, '_> {
+    /// Get the deserializer of a value that is not nested.
+    fn value(self) -> Deserializer<'a> {
+        match self.leaf {
+            Some((leaf, read)) => {
+                read.set(true);
+                leaf
+            }
+            None => self.de,
+        }
+    }
+}
+
+macro_rules! forward_to_inner {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, vis: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.value().$method(vis)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for MapValueDeserializer<'_, '_> {
+    type Error = Error;
+
+    forward_to_inner! {
+        deserialize_bool
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char
+        deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_unit deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_any<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // Subtrees are visited as maps.
+        if !self.de.node.child_keys().is_empty() {
+            self.nested.set(true);
+        }
+        if self.buffered {
+            let de = &self.de;
+            let name = de.cfg.env_name(&de.path);
+            de.state.buffered.borrow_mut().insert(name);
+        }
+        self.de.deserialize_any(vis)
+    }
+
+    fn deserialize_seq<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.de.node.has_indexed_children() {
+            self.nested.set(true);
+            return self.de.deserialize_seq(vis);
+        }
+        self.value().deserialize_seq(vis)
+    }
+
+    fn deserialize_option<V>(mut self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.expand()?;
+        if self.de.node.is_empty() {
+            vis.visit_none()
+        } else {
+            vis.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.value().deserialize_unit_struct(name, vis)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        vis: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        vis.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.de.node.has_indexed_children() {
+            self.nested.set(true);
+            return self.de.deserialize_tuple(len, vis);
+        }
+        self.value().deserialize_tuple(len, vis)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        vis: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, vis)
+    }
+
+    fn deserialize_map<V>(self, vis: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.nested.set(true);
+        self.de.deserialize_map(vis)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        vis: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.nested.set(true);
+        self.de.deserialize_struct(name, fields, vis)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        vis: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.value().deserialize_enum(name, variants, vis)
+    }
+}
+
+struct EnumAccessor<'a> {
+    variants: &'static [&'static str],
+    node: Node,
+    cfg: &'a EnvDeserializer,
+    state: &'a State,
+    path: String,
+}
+
+impl<'a> EnumAccessor<'a> {
+    fn new(
+        variants: &'static [&'static str],
+        node: Node,
+        cfg: &'a EnvDeserializer,
+        state: &'a State,
+        path: String,
+    ) -> Self {
+        Self {
+            variants,
+            node,
+            cfg,
+            state,
+            path,
+        }
+    }
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for EnumAccessor<'a> {
+    type Error = Error;
+    type Variant = VariantAccessor<'a>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let key = *self
+            .variants
+            .iter()
+            .find(|key| self.node.value() == **key)
+            .ok_or_else(|| de::Error::unknown_variant(self.node.value(), self.variants))?;
+
+        let variant = VariantAccessor::new(self.node, self.cfg, self.state, self.path);
+        Ok((seed.deserialize(key.into_deserializer())?, variant))
+    }
+}
+
+struct VariantAccessor<'a> {
+    node: Node,
+    cfg: &'a EnvDeserializer,
+    state: &'a State,
+    path: String,
+}
+
+impl<'a> VariantAccessor<'a> {
+    fn new(node: Node, cfg: &'a EnvDeserializer, state: &'a State, path: String) -> Self {
+        Self {
+            node,
+            cfg,
+            state,
+            path,
+        }
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccessor<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        if self.node.has_children() {
+            return Err(de::Error::custom("variant is not unit"));
+        }
+        Ok(())
+    }
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(Deserializer::new(
+            self.node, self.cfg, self.state, self.path,
+        ))
+    }
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::custom("tuple variant is not supported"))
+    }
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let keys = fields.iter().map(|v| v.to_string()).collect();
+
+        let (cfg, state, path) = (self.cfg, self.state, self.path.clone());
+        visitor
+            .visit_map(MapAccessor::new(keys, self.node, cfg, state, self.path))
+            .map_err(|err| cfg.field_error(state, &path, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(Deserialize, Default, PartialEq, Debug)]
+    #[serde(default)]
+    struct TestStruct {
+        a: i64,
+        b: bool,
+        c: String,
+        d: EmbedStruct,
+    }
+
+    #[derive(Deserialize, Default, PartialEq, Debug)]
+    #[serde(default)]
+    struct EmbedStruct {
+        aa: f32,
+        bb: String,
+    }
+
+    #[test]
+    fn test_from_env() {
+        temp_env::with_vars(
+            vec![
+                ("A", Some("123")),
+                ("B", Some("true")),
+                ("C", Some("Hello, test")),
+                ("D_AA", Some("1.2")),
+                ("D_BB", Some("Hello, embed")),
+            ],
+            || {
+                let t: TestStruct = from_env().expect("must success");
+                assert_eq!(
+                    t,
+                    TestStruct {
+                        a: 123,
+                        b: true,
+                        c: "Hello, test".to_string(),
+                        d: EmbedStruct {
+                            aa: 1.2,
+                            bb: "Hello, embed".to_string()
+                        }
+                    }
+                )
+            },
+        )
+    }
+
+    /// This test is ported from [softprops/envy](https://github.com/softprops/envy/blob/801d81e7c3e443470e110bf4e34460acba113476/src/lib.rs#L410)
+    #[derive(Deserialize, Debug, PartialEq, Eq)]
+    struct Foo {
+        bar: String,
+        baz: bool,
+        zoom: Option<u16>,
+        doom: Vec<u64>,
+        boom: Vec<String>,
+        #[serde(default = "default_kaboom")]
+        kaboom: u16,
+        #[serde(default)]
+        debug_mode: bool,
+        provided: Option<String>,
+        newtype: CustomNewType,
+        boom_zoom: bool,
+        #[serde(default = "default_bool")]
+        mode_xx: bool,
+    }
+
+    fn default_bool() -> bool {
+        true
+    }
+
+    fn default_kaboom() -> u16 {
+        8080
+    }
+
+    #[derive(Deserialize, Debug, PartialEq, Eq, Default)]
+    struct CustomNewType(u32);
+
+    #[test]
+    fn test_ported_from_envy() {
+        temp_env::with_vars(
+            vec![
+                ("BAR", Some("test")),
+                ("BAZ", Some("true")),
+                ("DOOM", Some("1, 2, 3 ")),
+                // Empty string should result in empty vector.
+                ("BOOM", Some("")),
+                ("SIZE", Some("small")),
+                ("PROVIDED", Some("test")),
+                ("NEWTYPE", Some("42")),
+                ("boom_zoom", Some("true")),
+                ("mode_xx", Some("false")),
+            ],
+            || {
+                let actual: Foo = from_env().expect("must success");
+                assert_eq!(
+                    actual,
+                    Foo {
+                        bar: String::from("test"),
+                        baz: true,
+                        zoom: None,
+                        doom: vec![1, 2, 3],
+                        boom: vec![],
+                        kaboom: 8080,
+                        debug_mode: false,
                         provided: Some(String::from("test")),
                         newtype: CustomNewType(42),
                         boom_zoom: true,
@@ -708,348 +2712,2000 @@ mod tests {
                     }
                 )
             },
-        )
+        )
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct TestStructAlias {
+        #[serde(alias = "meta_log_level")]
+        log_level: String,
+    }
+
+    // We are not support alias now.
+    #[test]
+    #[ignore]
+    fn test_from_env_alias() {
+        temp_env::with_vars(vec![("meta_log_level", Some("DEBUG"))], || {
+            let t: TestStructAlias = from_env().expect("must success");
+            assert_eq!(
+                t,
+                TestStructAlias {
+                    log_level: "DEBUG".to_string()
+                }
+            )
+        })
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct TestStructFlat {
+        meta_log_level: String,
+    }
+
+    #[test]
+    fn test_from_env_flat() {
+        temp_env::with_vars(vec![("meta_log_level", Some("DEBUG"))], || {
+            let t: TestStructFlat = from_env().expect("must success");
+            assert_eq!(
+                t,
+                TestStructFlat {
+                    meta_log_level: "DEBUG".to_string()
+                }
+            )
+        })
+    }
+
+    #[test]
+    fn test_from_env_flat_upper() {
+        temp_env::with_vars(vec![("META_LOG_LEVEL", Some("DEBUG"))], || {
+            let t: TestStructFlat = from_env().expect("must success");
+            assert_eq!(
+                t,
+                TestStructFlat {
+                    meta_log_level: "DEBUG".to_string()
+                }
+            )
+        })
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct TestStructFlatWithDefault {
+        meta_log_level: String,
+    }
+
+    impl Default for TestStructFlatWithDefault {
+        fn default() -> Self {
+            Self {
+                meta_log_level: "INFO".to_string(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_env_flat_with_default() {
+        temp_env::with_vars(vec![("meta_log_level", Some("DEBUG"))], || {
+            let t: TestStructFlatWithDefault = from_env().expect("must success");
+            assert_eq!(
+                t,
+                TestStructFlatWithDefault {
+                    meta_log_level: "DEBUG".to_string()
+                }
+            )
+        })
+    }
+
+    #[test]
+    fn test_from_env_flat_upper_with_default() {
+        temp_env::with_vars(vec![("META_LOG_LEVEL", Some("DEBUG"))], || {
+            let t: TestStructFlatWithDefault = from_env().expect("must success");
+            assert_eq!(
+                t,
+                TestStructFlatWithDefault {
+                    meta_log_level: "DEBUG".to_string()
+                }
+            )
+        })
+    }
+
+    #[test]
+    fn test_from_env_as_map() {
+        temp_env::with_vars(vec![("METASRV_LOG_LEVEL", Some("DEBUG"))], || {
+            let t: HashMap<String, String> = from_env().expect("must success");
+            assert_eq!(t["metasrv_log_level"], "DEBUG".to_string())
+        })
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct EnumNewtype {
+        bar: String,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct ExternallyEnumStruct {
+        foo: ExternallyEnum,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum ExternallyEnum {
+        X,
+        Y(EnumNewtype),
+        Z { a: i32 },
+    }
+
+    #[test]
+    fn test_from_env_externally_enum() {
+        temp_env::with_vars(vec![("FOO", Some("X"))], || {
+            let t: ExternallyEnumStruct = from_env().expect("must success");
+            assert_eq!(t.foo, ExternallyEnum::X)
+        });
+
+        temp_env::with_vars(vec![("FOO", Some("Y")), ("FOO_BAR", Some("xxx"))], || {
+            let t: ExternallyEnumStruct = from_env().expect("must success");
+            assert_eq!(
+                t.foo,
+                ExternallyEnum::Y(EnumNewtype {
+                    bar: "xxx".to_string()
+                })
+            )
+        });
+
+        temp_env::with_vars(vec![("FOO", Some("Z")), ("FOO_A", Some("1"))], || {
+            let t: ExternallyEnumStruct = from_env().expect("must success");
+            assert_eq!(t.foo, ExternallyEnum::Z { a: 1 })
+        });
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct TriggerDeserializeAny {
+        foo: TriggerDeserializeAnyEnum,
+    }
+
+    #[serde_with::serde_as]
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum TriggerDeserializeAnyEnum {
+        Json(#[serde_as(as = "serde_with::json::JsonString")] Vec<String>),
+        CommaSeparated(
+            #[serde_as(
+                deserialize_as = "serde_with::StringWithSeparator<serde_with::formats::CommaSeparator, String>"
+            )]
+            Vec<String>,
+        ),
+        NonJSON(Vec<String>),
+    }
+
+    #[test]
+    fn test_from_env_deserialize_any() {
+        temp_env::with_vars(vec![("FOO", Some("X"))], || {
+            let t: TriggerDeserializeAny = from_env().expect("must success");
+            assert_eq!(
+                t.foo,
+                TriggerDeserializeAnyEnum::CommaSeparated(vec!["X".to_string()])
+            )
+        });
+
+        temp_env::with_vars(vec![("FOO", Some("X,Y"))], || {
+            let t: TriggerDeserializeAny = from_env().expect("must success");
+            assert_eq!(
+                t.foo,
+                TriggerDeserializeAnyEnum::CommaSeparated(vec!["X".to_string(), "Y".to_string()])
+            )
+        });
+
+        temp_env::with_vars(vec![("FOO", Some("[\"X\",\"Y\"]"))], || {
+            let t: TriggerDeserializeAny = from_env().expect("must success");
+            assert_eq!(
+                t.foo,
+                TriggerDeserializeAnyEnum::Json(vec!["X".to_string(), "Y".to_string()])
+            )
+        });
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct InternallyEnumStruct {
+        foo: InternallyEnum,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(tag = "type")]
+    enum InternallyEnum {
+        X,
+        Y(EnumNewtype),
+        Z { a: i32 },
+    }
+
+    // Currently Internally / Adjacently / Untagged enum is not support by the following issues
+    // https://github.com/serde-rs/serde/issues/2187
+    #[test]
+    #[ignore]
+    fn test_from_env_internally_enum() {
+        temp_env::with_vars(vec![("FOO_TYPE", Some("X"))], || {
+            let t: InternallyEnumStruct = from_env().expect("must success");
+            assert_eq!(t.foo, InternallyEnum::X)
+        });
+
+        temp_env::with_vars(
+            vec![("FOO_TYPE", Some("Y")), ("FOO_BAR", Some("xxx"))],
+            || {
+                let t: InternallyEnumStruct = from_env().expect("must success");
+                assert_eq!(
+                    t.foo,
+                    InternallyEnum::Y(EnumNewtype {
+                        bar: "xxx".to_string()
+                    })
+                )
+            },
+        );
+
+        temp_env::with_vars(vec![("FOO_TYPE", Some("Z")), ("FOO_A", Some("1"))], || {
+            let t: InternallyEnumStruct = from_env().expect("must success");
+            assert_eq!(t.foo, InternallyEnum::Z { a: 1 })
+        });
+    }
+
+    #[derive(Deserialize, PartialEq, Debug, Eq)]
+    struct DoubleOptionOuter {
+        inner: Option<DoubleOptionInner>,
+    }
+
+    #[derive(Deserialize, PartialEq, Debug, Eq)]
+    struct DoubleOptionInner {
+        val: Option<u8>,
+    }
+
+    #[test]
+    fn double_option() {
+        temp_env::with_var("INNER_VAL", Some("2"), || {
+            let t: DoubleOptionOuter = from_env().expect("must success");
+            assert_eq!(
+                t,
+                DoubleOptionOuter {
+                    inner: Some(DoubleOptionInner { val: Some(2) })
+                }
+            )
+        })
+    }
+
+    #[test]
+    fn inner_mapping_with_enum_keys() {
+        #[derive(Debug, Deserialize, Eq, Hash, PartialEq)]
+        enum MappingKey {
+            Option1,
+            Option2,
+        }
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Mapping {
+            val: HashMap<MappingKey, String>,
+        }
+
+        let env = vec![("VAL_OPTION1", "FOO"), ("VAL_OPTION2", "BAR")];
+        let t: Mapping = from_iter(env).expect("must succeed");
+        assert_eq!(
+            t,
+            Mapping {
+                val: HashMap::from_iter(vec![
+                    (MappingKey::Option1, "FOO".to_string()),
+                    (MappingKey::Option2, "BAR".to_string())
+                ])
+            }
+        );
+    }
+
+    #[test]
+    fn enum_with_unused_envs() {
+        temp_env::with_vars(vec![("FOO", Some("X")), ("FOO_Z_A", Some("20"))], || {
+            let t: ExternallyEnumStruct = from_env().expect("must success");
+            assert_eq!(t.foo, ExternallyEnum::X)
+        });
+
+        temp_env::with_vars(
+            vec![
+                ("FOO", Some("Y")),
+                ("FOO_A", Some("20")),
+                ("FOO_BAR", Some("test")),
+            ],
+            || {
+                let t: ExternallyEnumStruct = from_env().expect("must success");
+                assert_eq!(
+                    t.foo,
+                    ExternallyEnum::Y(EnumNewtype {
+                        bar: "test".to_string()
+                    })
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn test_from_iter_with_separator() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Db {
+            max_conns: u32,
+        }
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            log_level: String,
+            db: Db,
+            labels: HashMap<String, String>,
+        }
+
+        let env = vec![
+            ("APP__LOG_LEVEL", "debug"),
+            ("APP__DB__MAX_CONNS", "10"),
+            ("APP__LABELS__TEAM_NAME", "infra"),
+        ];
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .separator("__")
+            .from_iter(env)
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                log_level: "debug".to_string(),
+                db: Db { max_conns: 10 },
+                labels: HashMap::from_iter(vec![("team_name".to_string(), "infra".to_string()),]),
+            }
+        );
+
+        let err = EnvDeserializer::new()
+            .separator("")
+            .from_iter::<_, _, Config>(vec![("A", "B")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "separator must not be empty");
+    }
+
+    #[test]
+    fn test_env_deserializer_list_delimiter() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            hosts: Vec<String>,
+            pair: (u16, u16),
+        }
+
+        let env = vec![("HOSTS", "a,1; b,2"), ("PAIR", "80;443")];
+        let t: Config = EnvDeserializer::new()
+            .list_delimiter(";")
+            .from_iter(env)
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                hosts: vec!["a,1".to_string(), "b,2".to_string()],
+                pair: (80, 443),
+            }
+        );
+
+        let err = EnvDeserializer::new()
+            .list_delimiter("")
+            .from_iter::<_, _, Config>(vec![("PAIR", "80")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "list delimiter must not be empty");
+
+        let err = EnvDeserializer::new()
+            .list_delimiters(Vec::<String>::new())
+            .from_iter::<_, _, Config>(vec![("PAIR", "80")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "list delimiter must not be empty");
+
+        let t: Config = EnvDeserializer::new()
+            .list_delimiters(Vec::<String>::new())
+            .list_delimiter(";")
+            .from_iter(vec![("HOSTS", "a;b"), ("PAIR", "80;443")])
+            .expect("must succeed");
+        assert_eq!(t.hosts, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn test_from_iter_map_of_structs() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct DbConfig {
+            host: String,
+            port: u16,
+        }
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            db: HashMap<String, DbConfig>,
+        }
+
+        let env = vec![
+            ("DB_PRIMARY_HOST", "10.0.0.1"),
+            ("DB_PRIMARY_PORT", "5432"),
+            ("DB_REPLICA_HOST", "10.0.0.2"),
+            ("DB_REPLICA_PORT", "5433"),
+        ];
+        let t: Config = from_iter(env).expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                db: HashMap::from_iter(vec![
+                    (
+                        "primary".to_string(),
+                        DbConfig {
+                            host: "10.0.0.1".to_string(),
+                            port: 5432
+                        }
+                    ),
+                    (
+                        "replica".to_string(),
+                        DbConfig {
+                            host: "10.0.0.2".to_string(),
+                            port: 5433
+                        }
+                    ),
+                ]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_from_iter_map_keys_with_separator() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            labels: HashMap<String, String>,
+            hosts: HashMap<String, Vec<String>>,
+        }
+
+        let env = vec![
+            ("LABELS_COST_CENTER", "ops"),
+            ("LABELS_TEAM", "core"),
+            ("LABELS_TEAM_NAME", "infra"),
+            ("HOSTS_EU_WEST", "a,b"),
+        ];
+        let (t, unused): (Config, _) = EnvDeserializer::new()
+            .from_iter_with_unused(env)
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                labels: HashMap::from_iter(vec![
+                    ("cost_center".to_string(), "ops".to_string()),
+                    ("team".to_string(), "core".to_string()),
+                    ("team_name".to_string(), "infra".to_string()),
+                ]),
+                hosts: HashMap::from_iter(vec![(
+                    "eu_west".to_string(),
+                    vec!["a".to_string(), "b".to_string()]
+                )]),
+            }
+        );
+        assert!(unused.is_empty());
+    }
+
+    #[test]
+    fn test_from_iter_indexed_seq() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Pair(String, u16);
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            servers: Vec<Server>,
+            pair: Pair,
+            ports: [u16; 2],
+        }
+
+        let mut env: Vec<(String, String)> = (0..11)
+            .flat_map(|i| {
+                [
+                    (format!("SERVERS_{i}_HOST"), format!("host-{i}")),
+                    (format!("SERVERS_{i}_PORT"), format!("{}", 8000 + i)),
+                ]
+            })
+            .collect();
+        env.push(("PAIR_0".to_string(), "a".to_string()));
+        env.push(("PAIR_1".to_string(), "80".to_string()));
+        env.push(("PORTS_1".to_string(), "443".to_string()));
+        env.push(("PORTS_0".to_string(), "80".to_string()));
+
+        let t: Config = from_iter(env).expect("must succeed");
+        assert_eq!(
+            t.servers,
+            (0..11)
+                .map(|i| Server {
+                    host: format!("host-{i}"),
+                    port: 8000 + i,
+                })
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(t.pair, Pair("a".to_string(), 80));
+        assert_eq!(t.ports, [80, 443]);
+
+        let env = vec![("SERVERS_0_HOST", "a"), ("SERVERS_2_HOST", "b")];
+        let err =
+            from_iter::<_, _, HashMap<String, Vec<HashMap<String, String>>>>(env).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `SERVERS`: missing index 1 in sequence, found index 2"
+        );
     }
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    struct TestStructAlias {
-        #[serde(alias = "meta_log_level")]
-        log_level: String,
+    #[test]
+    fn test_env_deserializer_list_options() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            patterns: Vec<String>,
+            #[serde(default)]
+            pair: Option<(String, String)>,
+        }
+
+        let env = vec![("PATTERNS", r"^a\,b$, c\\ ,, d")];
+        let t: Config = EnvDeserializer::new()
+            .list_escape(ListEscape::Backslash)
+            .from_iter(env)
+            .expect("must succeed");
+        assert_eq!(t.patterns, vec!["^a,b$", r"c\", "d"]);
+
+        let env = vec![("PATTERNS", r#""http://a/?x=1,2";" b ";;c"#)];
+        let t: Config = EnvDeserializer::new()
+            .list_delimiter(";")
+            .list_escape(ListEscape::Quote)
+            .keep_empty_list_elements(true)
+            .from_iter(env)
+            .expect("must succeed");
+        assert_eq!(t.patterns, vec!["http://a/?x=1,2", " b ", "", "c"]);
+
+        let env = vec![("PATTERNS", "a b"), ("PAIR", " x , ")];
+        let t: Config = EnvDeserializer::new()
+            .trim_list_elements(false)
+            .from_iter(env)
+            .expect("must succeed");
+        assert_eq!(t.patterns, vec!["a b"]);
+        assert_eq!(t.pair, Some((" x ".to_string(), " ".to_string())));
+
+        let env = vec![("PATTERNS", "a b"), ("PAIR", "x,,y")];
+        let t: Config = EnvDeserializer::new()
+            .keep_empty_list_elements(false)
+            .from_iter(env)
+            .expect("must succeed");
+        assert_eq!(t.pair, Some(("x".to_string(), "y".to_string())));
     }
 
-    // We are not support alias now.
     #[test]
-    #[ignore]
-    fn test_from_env_alias() {
-        temp_env::with_vars(vec![("meta_log_level", Some("DEBUG"))], || {
-            let t: TestStructAlias = from_env().expect("must success");
-            assert_eq!(
-                t,
-                TestStructAlias {
-                    log_level: "DEBUG".to_string()
-                }
-            )
-        })
+    fn test_from_iter_nested_list() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            hosts: Vec<(String, u16)>,
+            #[serde(default)]
+            matrix: Vec<Vec<u32>>,
+        }
+
+        let env = vec![("HOSTS", "a:80, b:81")];
+        let t: Config = from_iter(env).expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                hosts: vec![("a".to_string(), 80), ("b".to_string(), 81)],
+                matrix: vec![],
+            }
+        );
+
+        let env = vec![("HOSTS", "a,80"), ("MATRIX", "1,2;3,4")];
+        let t: Config = EnvDeserializer::new()
+            .list_delimiters([";", ","])
+            .from_iter(env)
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                hosts: vec![("a".to_string(), 80)],
+                matrix: vec![vec![1, 2], vec![3, 4]],
+            }
+        );
+
+        let env = vec![("HOSTS", "a:80"), ("MATRIX", "1")];
+        let err = EnvDeserializer::new()
+            .list_delimiters([","])
+            .from_iter::<_, _, Config>(env)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `HOSTS`: no list delimiter configured for nesting level 1"
+        );
     }
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    struct TestStructFlat {
-        meta_log_level: String,
+    #[test]
+    fn test_env_deserializer_case_sensitive() {
+        #[derive(Debug, Deserialize, Eq, Hash, PartialEq)]
+        enum Tier {
+            Gold,
+            #[serde(rename = "GOLD")]
+            GoldUpper,
+        }
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[allow(non_snake_case)]
+        struct Config {
+            Name: String,
+            #[serde(default)]
+            name: Option<String>,
+            labels: HashMap<String, String>,
+            tiers: HashMap<Tier, u8>,
+        }
+
+        let env = vec![
+            ("Name", "a"),
+            ("labels_Team", "x"),
+            ("labels_TEAM", "y"),
+            ("tiers_Gold", "1"),
+            ("tiers_GOLD", "2"),
+        ];
+        let t: Config = EnvDeserializer::new()
+            .case_sensitive(true)
+            .from_iter(env)
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                Name: "a".to_string(),
+                name: None,
+                labels: HashMap::from_iter(vec![
+                    ("Team".to_string(), "x".to_string()),
+                    ("TEAM".to_string(), "y".to_string()),
+                ]),
+                tiers: HashMap::from_iter(vec![(Tier::Gold, 1), (Tier::GoldUpper, 2)]),
+            }
+        );
+
+        let env = vec![("NAME", "a"), ("labels_Team", "x"), ("tiers_Gold", "1")];
+        let err = EnvDeserializer::new()
+            .case_sensitive(true)
+            .from_iter::<_, _, Config>(env)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "env `Name`: missing field `Name`");
     }
 
     #[test]
-    fn test_from_env_flat() {
-        temp_env::with_vars(vec![("meta_log_level", Some("DEBUG"))], || {
-            let t: TestStructFlat = from_env().expect("must success");
-            assert_eq!(
-                t,
-                TestStructFlat {
-                    meta_log_level: "DEBUG".to_string()
-                }
-            )
-        })
+    fn test_from_iter_with_prefix_case_insensitive() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            #[serde(default)]
+            port: u16,
+        }
+
+        let env = vec![("APP_NAME", "a"), ("app_port", "1")];
+        let t: Config = from_iter_with_prefix(env, "app").expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                name: "a".to_string(),
+                port: 1
+            }
+        );
+
+        let env = vec![("APP_NAME", "a")];
+        let err = EnvDeserializer::new()
+            .prefix("app")
+            .case_sensitive(true)
+            .from_iter::<_, _, Config>(env)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `app_name`: missing field `name` (no env found with prefix `app_`)"
+        );
     }
 
     #[test]
-    fn test_from_env_flat_upper() {
-        temp_env::with_vars(vec![("META_LOG_LEVEL", Some("DEBUG"))], || {
-            let t: TestStructFlat = from_env().expect("must success");
+    fn test_env_deserializer_empty_values() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: Option<String>,
+            tags: Option<Vec<String>>,
+            port: Option<u16>,
+        }
+
+        let env = vec![("NAME", ""), ("TAGS", ""), ("PORT", "null")];
+        let t: Config = from_iter(env[..2].to_vec()).expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                name: None,
+                tags: None,
+                port: None,
+            }
+        );
+
+        let t: Config = EnvDeserializer::new()
+            .empty_values(EmptyValues::Empty)
+            .null_values(["null", "none"])
+            .from_iter(env.clone())
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                name: Some("".to_string()),
+                tags: Some(vec![]),
+                port: None,
+            }
+        );
+
+        let err = EnvDeserializer::new()
+            .empty_values(EmptyValues::Error)
+            .prefix("APP")
+            .from_iter::<_, _, Config>(vec![("EMPTY", ""), ("APP_NAME", ""), ("APP_PORT", "1")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "env `APP_NAME`: value must not be empty");
+
+        let err = EnvDeserializer::new()
+            .empty_values(EmptyValues::Error)
+            .from_iter::<_, _, Config>(vec![("PORT", "1"), ("TAGS", "")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "env `TAGS`: value must not be empty");
+
+        // Only empty values read by fields are rejected.
+        let t: Config = EnvDeserializer::new()
+            .empty_values(EmptyValues::Error)
+            .from_iter(vec![("PORT", "1"), ("UNRELATED", "")])
+            .expect("must succeed");
+        assert_eq!(t.port, Some(1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_from_env_non_unicode() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            raw: OsString,
+            bytes: Vec<u8>,
+            name: String,
+            path: std::path::PathBuf,
+        }
+
+        let invalid = || OsString::from_vec(vec![b'a', 0xff, b'b']);
+        let vars = vec![
+            (OsString::from("TEST_OS_RAW"), Some(invalid())),
+            (OsString::from("TEST_OS_BYTES"), Some(invalid())),
+            (OsString::from("TEST_OS_NAME"), Some(OsString::from("x"))),
+            (OsString::from("TEST_OS_PATH"), Some(OsString::from("/tmp"))),
+            (OsString::from("TEST_OTHER_BAD"), Some(invalid())),
+            (OsString::from_vec(b"TEST_\xff".to_vec()), Some(invalid())),
+        ];
+        temp_env::with_vars(vars, || {
+            let t: Config = from_env_with_prefix("TEST_OS").expect("must success");
             assert_eq!(
                 t,
-                TestStructFlat {
-                    meta_log_level: "DEBUG".to_string()
+                Config {
+                    raw: invalid(),
+                    bytes: invalid().into_vec(),
+                    name: "x".to_string(),
+                    path: "/tmp".into(),
                 }
             )
-        })
+        });
+
+        let vars = vec![
+            (OsString::from("TEST_OS_RAW"), Some(OsString::from("raw"))),
+            (OsString::from("TEST_OS_BYTES"), Some(OsString::from("1,2"))),
+            (OsString::from("TEST_OS_NAME"), Some(invalid())),
+            (OsString::from("TEST_OS_PATH"), Some(OsString::from("/tmp"))),
+        ];
+        temp_env::with_vars(vars, || {
+            let err = from_env_with_prefix::<Config>("TEST_OS").unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "env `TEST_OS_NAME`: value is not valid unicode"
+            );
+        });
+
+        let vars = vec![(
+            OsString::from_vec(b"TEST_OS_\xff".to_vec()),
+            Some(OsString::from("x")),
+        )];
+        temp_env::with_vars(vars, || {
+            let err = from_env_with_prefix::<Config>("TEST_OS").unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "env `TEST_OS_\u{fffd}`: name is not valid unicode"
+            );
+        });
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Flat {
+            test_os_name: String,
+        }
+
+        let vars = vec![
+            (OsString::from("TEST_OS_NAME"), Some(OsString::from("x"))),
+            (
+                OsString::from_vec(b"UNRELATED_\xff".to_vec()),
+                Some(invalid()),
+            ),
+        ];
+        temp_env::with_vars(vars, || {
+            let t: Flat = from_env().expect("must success");
+            assert_eq!(t.test_os_name, "x");
+        });
     }
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    struct TestStructFlatWithDefault {
-        meta_log_level: String,
+    #[test]
+    fn test_error_kinds() {
+        use std::error::Error as _;
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Mode {
+            Dev,
+            Prod,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Db {
+            #[serde(default)]
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            db: Db,
+            #[serde(default)]
+            mode: Option<Mode>,
+            #[serde(default)]
+            pair: Option<(u8, u8)>,
+        }
+
+        let err =
+            from_iter_with_prefix::<_, _, Config>(vec![("APP_DB_PORT", "abc")], "APP").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Parse);
+        assert_eq!(err.key(), Some("APP_DB_PORT"));
+        assert_eq!(err.value(), Some("abc"));
+        assert_eq!(err.expected(), Some("u16"));
+        assert_eq!(
+            err.to_string(),
+            "env `APP_DB_PORT`: failed to parse `abc` as u16: invalid digit found in string"
+        );
+        assert!(err.source().is_some());
+
+        let err =
+            from_iter_with_prefix::<_, _, Config>(vec![("APP_DB_HOST", "a")], "APP").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingField);
+        assert_eq!(err.key(), Some("APP_DB_PORT"));
+        assert_eq!(err.field(), Some("port"));
+        assert_eq!(err.to_string(), "env `APP_DB_PORT`: missing field `port`");
+
+        let err = from_iter_with_prefix::<_, _, Config>(
+            vec![("APP_DB_PORT", "1"), ("APP_MODE", "test")],
+            "APP",
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnknownVariant);
+        assert_eq!(err.key(), Some("APP_MODE"));
+        assert_eq!(err.value(), Some("test"));
+        assert_eq!(
+            err.to_string(),
+            "env `APP_MODE`: unknown variant `test`, expected `dev` or `prod`"
+        );
+
+        let err = from_iter_with_prefix::<_, _, Config>(
+            vec![("APP_DB_PORT", "1"), ("APP_PAIR", "1")],
+            "APP",
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidLength);
+        assert_eq!(err.key(), Some("APP_PAIR"));
+        assert!(err.source().is_none());
     }
 
-    impl Default for TestStructFlatWithDefault {
-        fn default() -> Self {
-            Self {
-                meta_log_level: "INFO".to_string(),
-            }
+    #[test]
+    fn test_env_deserializer_collect_errors() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Mode {
+            Dev,
+            Prod,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Db {
+            port: u16,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            #[serde(default)]
+            port: u16,
+            db: Db,
+            mode: Option<Mode>,
+        }
+
+        let env = vec![
+            ("APP_PORT", "abc"),
+            ("APP_DB_PORT", "x"),
+            ("APP_MODE", "test"),
+        ];
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .collect_errors(true)
+            .from_iter::<_, _, Config>(env)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Multiple);
+        let errors: Vec<_> = err
+            .errors()
+            .iter()
+            .map(|err| (err.kind(), err.key().expect("must have key")))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (ErrorKind::Parse, "APP_DB_PORT"),
+                (ErrorKind::UnknownVariant, "APP_MODE"),
+                (ErrorKind::Parse, "APP_PORT"),
+                (ErrorKind::MissingField, "APP_NAME"),
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "found 4 errors:\n  \
+             env `APP_DB_PORT`: failed to parse `x` as u16: invalid digit found in string\n  \
+             env `APP_MODE`: unknown variant `test`, expected `dev` or `prod`\n  \
+             env `APP_PORT`: failed to parse `abc` as u16: invalid digit found in string\n  \
+             env `APP_NAME`: missing field `name`"
+        );
+
+        // A single error is returned as is.
+        let env = vec![("APP_NAME", "a"), ("APP_PORT", "abc"), ("APP_DB_PORT", "1")];
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .collect_errors(true)
+            .from_iter::<_, _, Config>(env)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Parse);
+        assert_eq!(err.key(), Some("APP_PORT"));
+
+        // A required field that failed is reported once, not as missing as
+        // well. serde stops at the first missing field, so the fields after
+        // it are not checked.
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Pair {
+            a: std::num::NonZeroU32,
+            b: u16,
         }
+
+        let err = EnvDeserializer::new()
+            .collect_errors(true)
+            .from_iter::<_, _, Pair>([("A", "0")])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+        assert_eq!(err.key(), Some("A"));
+
+        // No value is made up for missing fields.
+        let err = EnvDeserializer::new()
+            .collect_errors(true)
+            .from_iter::<_, _, Pair>(Vec::<(String, String)>::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingField);
+        assert_eq!(err.to_string(), "env `A`: missing field `a`");
+
+        // A struct whose field failed is treated as unset in its parent.
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Service {
+            db: Option<Db>,
+            mode: Mode,
+            name: String,
+        }
+
+        let err = EnvDeserializer::new()
+            .collect_errors(true)
+            .from_iter::<_, _, Service>([("DB_PORT", "x"), ("MODE", "test")])
+            .unwrap_err();
+        let errors: Vec<_> = err
+            .errors()
+            .iter()
+            .map(|err| (err.kind(), err.key()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (ErrorKind::Parse, Some("DB_PORT")),
+                (ErrorKind::UnknownVariant, Some("MODE")),
+            ]
+        );
+
+        let env = vec![("APP_NAME", "a"), ("APP_DB_PORT", "1")];
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .collect_errors(true)
+            .from_iter(env)
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                name: "a".to_string(),
+                port: 0,
+                db: Db { port: 1 },
+                mode: None,
+            }
+        );
     }
 
     #[test]
-    fn test_from_env_flat_with_default() {
-        temp_env::with_vars(vec![("meta_log_level", Some("DEBUG"))], || {
-            let t: TestStructFlatWithDefault = from_env().expect("must success");
+    fn test_env_deserializer_secrets() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Driver {
+            Mysql,
+            Postgres,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Db {
+            password: u32,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            #[serde(default)]
+            db: Option<Db>,
+            #[serde(default)]
+            driver: Option<Driver>,
+            #[serde(default)]
+            tokens: Vec<String>,
+        }
+
+        let err =
+            from_iter_with_prefix::<_, _, Config>(vec![("APP_DB_PASSWORD", "hunter2")], "APP")
+                .unwrap_err();
+        assert_eq!(err.key(), Some("APP_DB_PASSWORD"));
+        assert_eq!(err.value(), None);
+        assert_eq!(
+            err.to_string(),
+            "env `APP_DB_PASSWORD`: failed to parse `***` as u32: invalid digit found in string"
+        );
+        assert!(!format!("{err:?}").contains("hunter2"));
+
+        // Messages that would show a short value are left out, not mangled.
+        for v in ["a", "in"] {
+            let err = from_iter_with_prefix::<_, _, Config>(vec![("APP_DB_PASSWORD", v)], "APP")
+                .unwrap_err();
             assert_eq!(
-                t,
-                TestStructFlatWithDefault {
-                    meta_log_level: "DEBUG".to_string()
-                }
-            )
-        })
+                err.to_string(),
+                "env `APP_DB_PASSWORD`: failed to parse `***` as u32: invalid value"
+            );
+        }
+
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .secrets(["app_driver"])
+            .from_iter::<_, _, Config>(vec![("APP_DRIVER", "sqlite")])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `APP_DRIVER`: unknown variant `***`, expected `mysql` or `postgres`"
+        );
+        assert!(!format!("{err:?}").contains("sqlite"));
+
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .list_escape(ListEscape::Quote)
+            .from_iter::<_, _, Config>(vec![("APP_TOKENS", r#"a,"b"#)])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `APP_TOKENS`: unclosed quote in list: ***"
+        );
+
+        // Patterns can be replaced.
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .secret_patterns(["*_KEY"])
+            .from_iter::<_, _, Config>(vec![("APP_DB_PASSWORD", "hunter2")])
+            .unwrap_err();
+        assert_eq!(err.value(), Some("hunter2"));
     }
 
     #[test]
-    fn test_from_env_flat_upper_with_default() {
-        temp_env::with_vars(vec![("META_LOG_LEVEL", Some("DEBUG"))], || {
-            let t: TestStructFlatWithDefault = from_env().expect("must success");
+    fn test_from_dotenv() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            port: u16,
+        }
+
+        let content = "APP_NAME=app\nAPP_PORT=1\n";
+        temp_env::with_vars([("APP_PORT", Some("2"))], || {
+            let t: Config = EnvDeserializer::new()
+                .prefix("APP")
+                .from_dotenv_str(content)
+                .expect("must succeed");
+            assert_eq!(t.port, 1);
+
+            let t: Config = EnvDeserializer::new()
+                .prefix("APP")
+                .merge_env(true)
+                .from_dotenv_str(content)
+                .expect("must succeed");
             assert_eq!(
                 t,
-                TestStructFlatWithDefault {
-                    meta_log_level: "DEBUG".to_string()
+                Config {
+                    name: "app".to_string(),
+                    port: 2
                 }
-            )
-        })
+            );
+        });
+
+        let dir = env::temp_dir();
+        let err = from_dotenv_file::<Config>(dir.join("serde-env-missing.env")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert!(std::error::Error::source(&err).is_some());
+
+        let path = dir.join("serde-env-invalid.env");
+        fs::write(&path, "APP_NAME='app").expect("must succeed");
+        let err = from_dotenv_file::<Config>(&path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Syntax);
+        assert_eq!(
+            err.to_string(),
+            format!("line 1: unclosed single quote (in `{}`)", path.display())
+        );
+    }
+
+    #[test]
+    fn test_env_deserializer_interpolate() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            url: String,
+            #[serde(default)]
+            port: Option<u16>,
+        }
+
+        let vars = [
+            ("HOST", "db"),
+            ("APP_URL", "http://${HOST}:$APP_PORT/$$"),
+            ("APP_PORT", "${PORT:-8080}"),
+            // Not read by any field, so never expanded.
+            ("OTHER", "${MISSING}"),
+            ("APP_NOTE", "cost $UNDEFINED"),
+            ("APP_PS1", "${debian_chroot:+($debian_chroot)}"),
+        ];
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .interpolate(true)
+            .from_iter(vars)
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                url: "http://db:8080/$".to_string(),
+                port: Some(8080),
+            }
+        );
+
+        // Unrelated env don't fail without a prefix either.
+        let t: Config = EnvDeserializer::new()
+            .interpolate(true)
+            .from_iter([
+                ("URL", "http://$HOST"),
+                ("HOST", "db"),
+                ("PS1", "${debian_chroot:+($debian_chroot)}"),
+            ])
+            .expect("must succeed");
+        assert_eq!(t.url, "http://db");
+
+        // Values are kept as is by default.
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .from_iter([("APP_URL", "${HOST}")])
+            .expect("must succeed");
+        assert_eq!(t.url, "${HOST}");
+
+        // Expanding to empty is the same as unset.
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .interpolate(true)
+            .from_iter([("APP_URL", "x"), ("APP_PORT", "${PORT:-}")])
+            .expect("must succeed");
+        assert_eq!(t.port, None);
+
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .interpolate(true)
+            .from_iter::<_, _, Config>([("APP_URL", "${APP_PORT}"), ("APP_PORT", "$HOST")])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Interpolation);
+        assert_eq!(err.key(), Some("APP_PORT"));
+        assert_eq!(
+            err.to_string(),
+            "env `APP_PORT`: referenced env `HOST` is not set"
+        );
+    }
+
+    #[test]
+    fn test_env_deserializer_file_vars() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Db {
+            password: String,
+            log_file: String,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            db: Db,
+            #[serde(default)]
+            token: Option<String>,
+        }
+
+        let path = env::temp_dir().join("serde-env-file-vars.txt");
+        fs::write(&path, "secret\r\n").expect("must succeed");
+        let path = path.to_str().expect("must be unicode");
+
+        let cfg = EnvDeserializer::new().prefix("APP").file_vars(true);
+        let t: Config = cfg
+            .from_iter([
+                ("APP_DB_PASSWORD_FILE", path),
+                ("APP_DB_LOG_FILE", "/var/log/db.log"),
+            ])
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                db: Db {
+                    password: "secret".to_string(),
+                    log_file: "/var/log/db.log".to_string(),
+                },
+                token: None,
+            }
+        );
+
+        let t: Config = cfg
+            .clone()
+            .separator("__")
+            .from_iter([
+                ("APP__DB__PASSWORD", "plain"),
+                ("APP__DB__LOG_FILE", "/var/log/db.log"),
+                ("APP__TOKEN_FILE", path),
+            ])
+            .expect("must succeed");
+        assert_eq!(t.db.password, "plain");
+        assert_eq!(t.token.as_deref(), Some("secret"));
+
+        // `_FILE` is not read unless enabled.
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .from_iter([
+                ("APP_DB_PASSWORD_FILE", path),
+                ("APP_DB_LOG_FILE", "/var/log/db.log"),
+            ])
+            .expect("must succeed");
+        assert_eq!(t.db.password, "");
+
+        let err = cfg
+            .from_iter::<_, _, Config>([
+                ("APP_DB_PASSWORD", "plain"),
+                ("APP_DB_PASSWORD_FILE", path),
+                ("APP_DB_LOG_FILE", "/var/log/db.log"),
+            ])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+        assert_eq!(
+            err.to_string(),
+            "env `APP_DB_PASSWORD`: `APP_DB_PASSWORD_FILE` is set as well, only one of them is allowed"
+        );
+
+        let missing = env::temp_dir().join("serde-env-missing.txt");
+        let err = cfg
+            .from_iter::<_, _, Config>([
+                (
+                    "APP_DB_PASSWORD_FILE",
+                    missing.to_str().expect("must be unicode"),
+                ),
+                ("APP_DB_LOG_FILE", "/var/log/db.log"),
+            ])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        assert_eq!(err.key(), Some("APP_DB_PASSWORD_FILE"));
+
+        // Values read from files are secret whatever the field is named.
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Api {
+            key: u32,
+        }
+
+        let path = env::temp_dir().join("serde-env-file-vars-key.txt");
+        fs::write(&path, "sk-live-abcdef\n").expect("must succeed");
+        let err = EnvDeserializer::new()
+            .file_vars(true)
+            .from_iter::<_, _, Api>([("KEY_FILE", path.to_str().expect("must be unicode"))])
+            .unwrap_err();
+        assert_eq!(err.key(), Some("KEY"));
+        assert_eq!(err.value(), None);
+        assert_eq!(
+            err.to_string(),
+            "env `KEY`: failed to parse `***` as u32: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn test_env_deserializer_unused() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Mode {
+            Fast,
+            Slow,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Db {
+            url: String,
+            hosts: Vec<String>,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            db: Db,
+            mode: Mode,
+            labels: HashMap<String, String>,
+            #[serde(default)]
+            debug: Option<bool>,
+        }
+
+        let vars = [
+            ("APP_DB", "db"),
+            ("APP_DB_URL", "postgres://db"),
+            ("APP_DB_HOSTS", "a,b"),
+            ("APP_DATABSE_URL", "postgres://typo"),
+            ("APP_MODE", "fast"),
+            ("APP_LABELS_TEAM", "core"),
+            ("APP_LABELS_TIER", "web"),
+            ("APP_DEBUGG", "true"),
+            ("OTHER", "1"),
+        ];
+        let (t, unused): (Config, _) = EnvDeserializer::new()
+            .prefix("APP")
+            .from_iter_with_unused(vars)
+            .expect("must succeed");
+        assert_eq!(t.mode, Mode::Fast);
+        assert_eq!(t.labels.len(), 2);
+        assert_eq!(unused, ["APP_DATABSE_URL", "APP_DB", "APP_DEBUGG"]);
+
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .deny_unused(true)
+            .from_iter::<_, _, Config>(vars)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Multiple);
+        assert_eq!(
+            err.to_string(),
+            "found 3 errors:\n  \
+             env `APP_DATABSE_URL`: not used by any field\n  \
+             env `APP_DB`: not used by any field\n  \
+             env `APP_DEBUGG`: not used by any field"
+        );
+
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .deny_unused(true)
+            .from_iter([
+                ("APP_DB_URL", "postgres://db"),
+                ("APP_DB_HOSTS", "a"),
+                ("APP_MODE", "slow"),
+                ("APP_LABELS_TEAM", "core"),
+                ("OTHER", "1"),
+            ])
+            .expect("must succeed");
+        assert_eq!(t.mode, Mode::Slow);
+
+        // A prefix that matches nothing is reported even if all fields have
+        // defaults.
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Defaults {
+            #[serde(default)]
+            x: u16,
+        }
+
+        let t: Defaults = EnvDeserializer::new()
+            .prefix("APP")
+            .from_iter([("OTHER_X", "1")])
+            .expect("must succeed");
+        assert_eq!(t.x, 0);
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .deny_unused(true)
+            .from_iter::<_, _, Defaults>([("OTHER_X", "1")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "no env found with prefix `APP_`");
     }
 
     #[test]
-    fn test_from_env_as_map() {
-        temp_env::with_vars(vec![("METASRV_LOG_LEVEL", Some("DEBUG"))], || {
-            let t: HashMap<String, String> = from_env().expect("must success");
-            assert_eq!(t["metasrv_log_level"], "DEBUG".to_string())
-        })
-    }
+    fn test_missing_field_suggestions() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Database {
+            url: String,
+        }
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    struct EnumNewtype {
-        bar: String,
-    }
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Db {
+            port: u16,
+            ports: Vec<u16>,
+        }
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    struct ExternallyEnumStruct {
-        foo: ExternallyEnum,
-    }
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Config {
+            database: Database,
+            #[serde(default)]
+            db: Option<Db>,
+        }
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    enum ExternallyEnum {
-        X,
-        Y(EnumNewtype),
-        Z { a: i32 },
+        let cfg = EnvDeserializer::new().prefix("APP");
+        let err = cfg
+            .from_iter::<_, _, Config>([("APP_DATABSE_URL", "postgres://db")])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingField);
+        assert_eq!(
+            err.to_string(),
+            "env `APP_DATABASE`: missing field `database` \
+             (`APP_DATABSE_URL` is set, did you mean `APP_DATABASE_URL`?)"
+        );
+
+        let err = cfg
+            .from_iter::<_, _, Config>([("APP_DATABASE_URLL", "postgres://db")])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `APP_DATABASE_URL`: missing field `url` \
+             (`APP_DATABASE_URLL` is set, did you mean `APP_DATABASE_URL`?)"
+        );
+
+        // Env read by other fields are not suggested.
+        let err = cfg
+            .from_iter::<_, _, Config>([
+                ("APP_DATABASE_URL", "postgres://db"),
+                ("APP_DB_PORTS", "1"),
+            ])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "env `APP_DB_PORT`: missing field `port`");
+
+        // Names too far away are not suggested.
+        let err = cfg
+            .from_iter::<_, _, Config>([("APP_DATA_URL", "postgres://db")])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `APP_DATABASE`: missing field `database`"
+        );
     }
 
     #[test]
-    fn test_from_env_externally_enum() {
-        temp_env::with_vars(vec![("FOO", Some("X"))], || {
-            let t: ExternallyEnumStruct = from_env().expect("must success");
-            assert_eq!(t.foo, ExternallyEnum::X)
-        });
+    fn test_uppercase_renames() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        struct Db {
+            max_conns: u32,
+            #[serde(rename = "Url")]
+            url: String,
+        }
 
-        temp_env::with_vars(vec![("FOO", Some("Y")), ("FOO_BAR", Some("xxx"))], || {
-            let t: ExternallyEnumStruct = from_env().expect("must success");
-            assert_eq!(
-                t.foo,
-                ExternallyEnum::Y(EnumNewtype {
-                    bar: "xxx".to_string()
-                })
-            )
-        });
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        struct Config {
+            #[serde(rename = "DATABASE_URL")]
+            database_url: String,
+            db: Db,
+            #[serde(default)]
+            log_level: Option<String>,
+        }
 
-        temp_env::with_vars(vec![("FOO", Some("Z")), ("FOO_A", Some("1"))], || {
-            let t: ExternallyEnumStruct = from_env().expect("must success");
-            assert_eq!(t.foo, ExternallyEnum::Z { a: 1 })
-        });
-    }
+        let vars = [
+            ("APP_DATABASE_URL", "postgres://db"),
+            ("APP_DB_MAX_CONNS", "10"),
+            ("app_db_url", "postgres://replica"),
+        ];
+        let expected = Config {
+            database_url: "postgres://db".to_string(),
+            db: Db {
+                max_conns: 10,
+                url: "postgres://replica".to_string(),
+            },
+            log_level: None,
+        };
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .from_iter(vars)
+            .expect("must succeed");
+        assert_eq!(t, expected);
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    struct TriggerDeserializeAny {
-        foo: TriggerDeserializeAnyEnum,
-    }
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .from_iter::<_, _, Config>([("APP_DATABASE_URL", "postgres://db")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "env `APP_DB`: missing field `DB`");
 
-    #[serde_with::serde_as]
-    #[derive(Deserialize, PartialEq, Debug)]
-    #[serde(untagged)]
-    enum TriggerDeserializeAnyEnum {
-        Json(#[serde_as(as = "serde_with::json::JsonString")] Vec<String>),
-        CommaSeparated(
-            #[serde_as(
-                deserialize_as = "serde_with::StringWithSeparator<serde_with::formats::CommaSeparator, String>"
-            )]
-            Vec<String>,
-        ),
-        NonJSON(Vec<String>),
+        // Renames are matched exactly if keys are case sensitive.
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .case_sensitive(true)
+            .from_iter::<_, _, Config>(vars)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "env `APP_DB_Url`: missing field `Url`");
     }
 
     #[test]
-    fn test_from_env_deserialize_any() {
-        temp_env::with_vars(vec![("FOO", Some("X"))], || {
-            let t: TriggerDeserializeAny = from_env().expect("must success");
-            assert_eq!(
-                t.foo,
-                TriggerDeserializeAnyEnum::CommaSeparated(vec!["X".to_string()])
-            )
-        });
+    fn test_camel_and_kebab_case_fields() {
+        #[derive(Deserialize, serde::Serialize, PartialEq, Debug)]
+        #[serde(rename_all = "kebab-case")]
+        struct Http {
+            max_body_size: u32,
+            #[serde(rename = "TLSCertPath")]
+            tls_cert_path: String,
+        }
 
-        temp_env::with_vars(vec![("FOO", Some("X,Y"))], || {
-            let t: TriggerDeserializeAny = from_env().expect("must success");
-            assert_eq!(
-                t.foo,
-                TriggerDeserializeAnyEnum::CommaSeparated(vec!["X".to_string(), "Y".to_string()])
-            )
-        });
+        #[derive(Deserialize, serde::Serialize, PartialEq, Debug)]
+        #[serde(rename_all = "camelCase")]
+        struct Config {
+            log_level: String,
+            http_server: Http,
+        }
 
-        temp_env::with_vars(vec![("FOO", Some("[\"X\",\"Y\"]"))], || {
-            let t: TriggerDeserializeAny = from_env().expect("must success");
-            assert_eq!(
-                t.foo,
-                TriggerDeserializeAnyEnum::Json(vec!["X".to_string(), "Y".to_string()])
-            )
-        });
-    }
+        let expected = Config {
+            log_level: "debug".to_string(),
+            http_server: Http {
+                max_body_size: 1024,
+                tls_cert_path: "/cert.pem".to_string(),
+            },
+        };
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .from_iter([
+                ("APP_LOG_LEVEL", "debug"),
+                ("APP_HTTP_SERVER_MAX_BODY_SIZE", "1024"),
+                ("APP_HTTP_SERVER_TLS_CERT_PATH", "/cert.pem"),
+            ])
+            .expect("must succeed");
+        assert_eq!(t, expected);
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    struct InternallyEnumStruct {
-        foo: InternallyEnum,
+        let vars = crate::to_env_with_prefix(&expected, "APP").expect("must succeed");
+        assert_eq!(vars[0], ("APP_LOG_LEVEL".to_string(), "debug".to_string()));
+        let t: Config = from_iter_with_prefix(vars, "APP").expect("must succeed");
+        assert_eq!(t, expected);
+
+        // With `__` as separator, words stay in the same key.
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .separator("__")
+            .from_iter([
+                ("APP__LOG_LEVEL", "debug"),
+                ("APP__HTTP_SERVER__MAX_BODY_SIZE", "1024"),
+                ("APP__HTTP_SERVER__TLS_CERT_PATH", "/cert.pem"),
+            ])
+            .expect("must succeed");
+        assert_eq!(t, expected);
     }
 
-    #[derive(Deserialize, PartialEq, Debug)]
-    #[serde(tag = "type")]
-    enum InternallyEnum {
-        X,
-        Y(EnumNewtype),
-        Z { a: i32 },
+    #[test]
+    fn test_deserialize_any_subtree() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(untagged)]
+        enum Backend {
+            Redis { url: String },
+            File { path: String },
+            Name(String),
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            backend: Backend,
+            cache: Backend,
+            extra: serde_json::Value,
+            #[serde(default)]
+            labels: HashMap<String, serde_json::Value>,
+        }
+
+        let vars = [
+            ("APP_BACKEND_PATH", "/data"),
+            ("APP_CACHE", "memory"),
+            ("APP_EXTRA_DEBUG", "true"),
+            ("APP_EXTRA_RETRY_MAX", "3"),
+            ("APP_EXTRA_RETRY_RATIO", "-0.5"),
+            ("APP_EXTRA_OFFSET", "-1"),
+            ("APP_EXTRA_NAME", "inf"),
+            ("APP_LABELS_ID", "007"),
+        ];
+        let t: Config = from_iter_with_prefix(vars, "APP").expect("must succeed");
+        assert_eq!(
+            t.backend,
+            Backend::File {
+                path: "/data".to_string()
+            }
+        );
+        assert_eq!(t.cache, Backend::Name("memory".to_string()));
+        assert_eq!(
+            t.extra,
+            serde_json::json!({
+                "debug": "true",
+                "name": "inf",
+                "offset": "-1",
+                "retry": {"max": "3", "ratio": "-0.5"},
+            })
+        );
+        // Values of maps are not inferred either.
+        assert_eq!(t.labels["id"], serde_json::json!("007"));
+
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .from_iter(vars)
+            .expect("must succeed");
+        assert_eq!(
+            t.extra,
+            serde_json::json!({
+                "debug": true,
+                "name": "inf",
+                "offset": -1,
+                "retry": {"max": 3, "ratio": -0.5},
+            })
+        );
+
+        let err = from_iter_with_prefix::<_, _, Config>(
+            [
+                ("APP_BACKEND_HOST", "localhost"),
+                ("APP_CACHE", "memory"),
+                ("APP_EXTRA", "1"),
+            ],
+            "APP",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `APP_BACKEND`: data did not match any variant of untagged enum Backend"
+        );
     }
 
-    // Currently Internally / Adjacently / Untagged enum is not support by the following issues
-    // https://github.com/serde-rs/serde/issues/2187
     #[test]
-    #[ignore]
-    fn test_from_env_internally_enum() {
-        temp_env::with_vars(vec![("FOO_TYPE", Some("X"))], || {
-            let t: InternallyEnumStruct = from_env().expect("must success");
-            assert_eq!(t.foo, InternallyEnum::X)
-        });
+    fn test_from_layers() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Db {
+            host: String,
+            port: u16,
+            name: String,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            db: Db,
+            debug: bool,
+        }
+
+        let path = env::temp_dir().join("serde-env-from-layers.env");
+        fs::write(&path, "APP_DB_HOST=file\nAPP_DB_PORT=1\n").expect("must succeed");
 
+        let layers = Layers::new()
+            .vars([
+                ("APP_DB_HOST", "default"),
+                ("APP_DB_NAME", "app"),
+                ("APP_DEBUG", "false"),
+            ])
+            .dotenv_file(&path)
+            .env()
+            .vars([("APP_DEBUG", "true")]);
         temp_env::with_vars(
-            vec![("FOO_TYPE", Some("Y")), ("FOO_BAR", Some("xxx"))],
+            [("APP_DB_PORT", Some("2")), ("APP_DEBUG", Some("false"))],
             || {
-                let t: InternallyEnumStruct = from_env().expect("must success");
+                let t: Config = EnvDeserializer::new()
+                    .prefix("APP")
+                    .from_layers(&layers)
+                    .expect("must succeed");
                 assert_eq!(
-                    t.foo,
-                    InternallyEnum::Y(EnumNewtype {
-                        bar: "xxx".to_string()
-                    })
-                )
+                    t,
+                    Config {
+                        db: Db {
+                            host: "file".to_string(),
+                            port: 2,
+                            name: "app".to_string(),
+                        },
+                        debug: true,
+                    }
+                );
             },
         );
 
-        temp_env::with_vars(vec![("FOO_TYPE", Some("Z")), ("FOO_A", Some("1"))], || {
-            let t: InternallyEnumStruct = from_env().expect("must success");
-            assert_eq!(t.foo, InternallyEnum::Z { a: 1 })
-        });
+        let layers = Layers::new().dotenv_file(env::temp_dir().join("serde-env-missing.env"));
+        let err = EnvDeserializer::new()
+            .from_layers::<Config>(&layers)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
     }
 
-    #[derive(Deserialize, PartialEq, Debug, Eq)]
-    struct DoubleOptionOuter {
-        inner: Option<DoubleOptionInner>,
-    }
+    #[test]
+    fn test_flatten() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Db {
+            host: String,
+            port: u16,
+            pool_size: u32,
+        }
 
-    #[derive(Deserialize, PartialEq, Debug, Eq)]
-    struct DoubleOptionInner {
-        val: Option<u8>,
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Backend {
+            Redis(String),
+            File(String),
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            #[serde(flatten)]
+            db: Db,
+            #[serde(flatten)]
+            backend: Backend,
+            #[serde(flatten)]
+            extra: HashMap<String, String>,
+        }
+
+        let vars = [
+            ("APP_NAME", "svc"),
+            ("APP_HOST", "localhost"),
+            ("APP_PORT", "5432"),
+            ("APP_POOL_SIZE", "10"),
+            ("APP_REDIS", "redis://cache"),
+            ("APP_ZONE_NAME", "eu"),
+        ];
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .from_iter(vars)
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Config {
+                name: "svc".to_string(),
+                db: Db {
+                    host: "localhost".to_string(),
+                    port: 5432,
+                    pool_size: 10,
+                },
+                backend: Backend::Redis("redis://cache".to_string()),
+                extra: HashMap::from_iter([("zone_name".to_string(), "eu".to_string())]),
+            }
+        );
+
+        let vars = [
+            ("APP_NAME", "svc"),
+            ("APP_HOST", "localhost"),
+            ("APP_PORT", "5432"),
+            ("APP_POOL_SIZE", "10"),
+            ("APP_FILE", "/data"),
+        ];
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .from_iter(vars)
+            .expect("must succeed");
+        assert_eq!(t.backend, Backend::File("/data".to_string()));
+        assert!(t.extra.is_empty());
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Limits {
+            max_conns: u32,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Service {
+            #[serde(flatten)]
+            limits: Limits,
+        }
+
+        let t: Service = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .from_iter([("APP_MAX_CONNS", "10")])
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Service {
+                limits: Limits { max_conns: 10 },
+            }
+        );
+
+        // Flattened maps of strings take every value as is.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Labeled {
+            name: String,
+            #[serde(flatten)]
+            labels: HashMap<String, String>,
+        }
+
+        let t: Labeled = EnvDeserializer::new()
+            .prefix("APP")
+            .from_iter([
+                ("APP_NAME", "svc"),
+                ("APP_ZONE_NAME", "eu"),
+                ("APP_DEBUG", "true"),
+                ("APP_RETRIES", "3"),
+            ])
+            .expect("must succeed");
+        assert_eq!(
+            t,
+            Labeled {
+                name: "svc".to_string(),
+                labels: HashMap::from_iter([
+                    ("zone_name".to_string(), "eu".to_string()),
+                    ("debug".to_string(), "true".to_string()),
+                    ("retries".to_string(), "3".to_string()),
+                ]),
+            }
+        );
     }
 
     #[test]
-    fn double_option() {
-        temp_env::with_var("INNER_VAL", Some("2"), || {
-            let t: DoubleOptionOuter = from_env().expect("must success");
-            assert_eq!(
-                t,
-                DoubleOptionOuter {
-                    inner: Some(DoubleOptionInner { val: Some(2) })
-                }
-            )
-        })
+    fn test_flatten_errors() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Db {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(tag = "kind", rename_all = "lowercase")]
+        enum Store {
+            S3 { bucket: String, retries: u8 },
+            Local { path: String },
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            #[serde(flatten)]
+            db: Db,
+            #[serde(flatten)]
+            store: Store,
+        }
+
+        let t: Config = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .from_iter([
+                ("APP_HOST", "localhost"),
+                ("APP_PORT", "5432"),
+                ("APP_KIND", "s3"),
+                ("APP_BUCKET", "logs"),
+                ("APP_RETRIES", "3"),
+            ])
+            .expect("must succeed");
+        assert_eq!(
+            t.store,
+            Store::S3 {
+                bucket: "logs".to_string(),
+                retries: 3
+            }
+        );
+
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .from_iter::<_, _, Config>([
+                ("APP_HOST", "localhost"),
+                ("APP_PORT", "abc"),
+                ("APP_KIND", "local"),
+                ("APP_PATH", "/data"),
+            ])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidType);
+        assert_eq!(err.key(), Some("APP_PORT"));
+        assert_eq!(err.value(), Some("abc"));
+        assert_eq!(
+            err.to_string(),
+            "env `APP_PORT`: invalid type: string \"abc\", expected u16"
+        );
+
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .from_iter::<_, _, Config>([("APP_HOST", "localhost"), ("APP_KIND", "local")])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingField);
+        assert_eq!(err.key(), Some("APP_PORT"));
+
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .collect_errors(true)
+            .from_iter::<_, _, Config>([("APP_PORT", "abc"), ("APP_KIND", "local")])
+            .unwrap_err();
+        let errors: Vec<_> = err
+            .errors()
+            .iter()
+            .map(|err| (err.kind(), err.key()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (ErrorKind::InvalidType, Some("APP_PORT")),
+                (ErrorKind::MissingField, Some("APP_HOST")),
+            ]
+        );
+
+        // serde doesn't tell which of the env it buffers the flattened
+        // fields read, so none of it passes as used.
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Service {
+            name: String,
+            #[serde(flatten)]
+            db: Db,
+        }
+
+        let vars = [
+            ("APP_NAME", "svc"),
+            ("APP_HOST", "localhost"),
+            ("APP_PORT", "5432"),
+            ("APP_TYPO", "1"),
+            ("APP_NAEM", "typo"),
+        ];
+        let err = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .deny_unused(true)
+            .from_iter::<_, _, Service>(vars)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "found 4 errors:\n  \
+             env `APP_HOST`: read for `#[serde(flatten)]` fields, can't tell if any uses it\n  \
+             env `APP_NAEM`: read for `#[serde(flatten)]` fields, can't tell if any uses it\n  \
+             env `APP_PORT`: read for `#[serde(flatten)]` fields, can't tell if any uses it\n  \
+             env `APP_TYPO`: read for `#[serde(flatten)]` fields, can't tell if any uses it"
+        );
+        assert!(err
+            .errors()
+            .iter()
+            .all(|err| err.kind() == ErrorKind::UnknownField));
+
+        let (t, unused): (Service, _) = EnvDeserializer::new()
+            .prefix("APP")
+            .infer_types(true)
+            .from_iter_with_unused(vars)
+            .expect("must succeed");
+        assert_eq!(t.name, "svc");
+        assert!(unused.is_empty());
     }
 
     #[test]
-    fn inner_mapping_with_enum_keys() {
+    fn double_inner_mapping_with_enum_keys() {
         #[derive(Debug, Deserialize, Eq, Hash, PartialEq)]
+        #[serde(rename_all = "lowercase")]
         enum MappingKey {
             Option1,
             Option2,
         }
+
+        #[derive(Debug, Deserialize, Eq, Hash, PartialEq)]
+        #[serde(rename_all = "lowercase")]
+        enum MappingKey2 {
+            Inner1,
+            Inner2,
+        }
+
         #[derive(Debug, Deserialize, Eq, PartialEq)]
         struct Mapping {
-            val: HashMap<MappingKey, String>,
+            val: HashMap<MappingKey, HashMap<MappingKey2, String>>,
         }
 
-        let env = vec![("VAL_OPTION1", "FOO"), ("VAL_OPTION2", "BAR")];
+        let env = vec![("VAL_OPTION1_INNER2", "FOO"), ("VAL_OPTION2_INNER1", "BAR")];
         let t: Mapping = from_iter(env).expect("must succeed");
         assert_eq!(
             t,
             Mapping {
                 val: HashMap::from_iter(vec![
-                    (MappingKey::Option1, "FOO".to_string()),
-                    (MappingKey::Option2, "BAR".to_string())
+                    (
+                        MappingKey::Option1,
+                        HashMap::from_iter(vec![(MappingKey2::Inner2, "FOO".to_string())])
+                    ),
+                    (
+                        MappingKey::Option2,
+                        HashMap::from_iter(vec![(MappingKey2::Inner1, "BAR".to_string())])
+                    ),
                 ])
             }
         );
     }
-
-    #[test]
-    fn enum_with_unused_envs() {
-        temp_env::with_vars(vec![("FOO", Some("X")), ("FOO_Z_A", Some("20"))], || {
-            let t: ExternallyEnumStruct = from_env().expect("must success");
-            assert_eq!(t.foo, ExternallyEnum::X)
-        });
-
-        temp_env::with_vars(
-            vec![
-                ("FOO", Some("Y")),
-                ("FOO_A", Some("20")),
-                ("FOO_BAR", Some("test")),
-            ],
-            || {
-                let t: ExternallyEnumStruct = from_env().expect("must success");
-                assert_eq!(
-                    t.foo,
-                    ExternallyEnum::Y(EnumNewtype {
-                        bar: "test".to_string()
-                    })
-                )
-            },
-        );
-    }
-
-    // TODO: not supported yet, refer to https://github.com/Xuanwo/serde-env/issues/49
-    //
-    // #[test]
-    // fn double_inner_mapping_with_enum_keys() {
-    //     #[derive(Debug, Deserialize, Eq, Hash, PartialEq)]
-    //     #[serde(rename_all = "lowercase")]
-    //     enum MappingKey {
-    //         Option1,
-    //         Option2,
-    //     }
-    //
-    //     #[derive(Debug, Deserialize, Eq, Hash, PartialEq)]
-    //     #[serde(rename_all = "lowercase")]
-    //     enum MappingKey2 {
-    //         Inner1,
-    //         Inner2,
-    //     }
-    //
-    //     #[derive(Debug, Deserialize, Eq, PartialEq)]
-    //     struct Mapping {
-    //         val: HashMap<MappingKey, HashMap<MappingKey2, String>>,
-    //     }
-    //
-    //     let env = vec![("VAL_OPTION1_INNER2", "FOO"), ("VAL_OPTION2_INNER1", "BAR")];
-    //     let t: Mapping = from_iter(env).expect("must succeed");
-    //     assert_eq!(
-    //         t,
-    //         Mapping {
-    //             val: HashMap::from_iter(vec![
-    //                 (
-    //                     MappingKey::Option1,
-    //                     HashMap::from_iter(vec![(MappingKey2::Inner2, "FOO".to_string())])
-    //                 ),
-    //                 (
-    //                     MappingKey::Option2,
-    //                     HashMap::from_iter(vec![(MappingKey2::Inner1, "BAR".to_string())])
-    //                 ),
-    //             ])
-    //         }
-    //     );
-    // }
 }
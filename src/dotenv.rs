@@ -0,0 +1,229 @@
+use crate::error::{Error, ErrorKind};
+
+/// Parse content of a `.env` file into key value pairs in order.
+///
+/// The syntax follows the common dotenv conventions:
+///
+/// - Blank lines and lines starting with `#` are skipped.
+/// - Keys may be prefixed with `export `.
+/// - Unquoted values are trimmed, and a `#` after whitespace starts a
+///   comment.
+/// - Single quoted values are kept as is.
+/// - Double quoted values support `\n`, `\r`, `\t`, `\"`, `\\` and `\$`
+///   escapes.
+/// - Quoted values can span multiple lines.
+pub(crate) fn parse(content: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut parser = Parser {
+        rest: content,
+        line: 1,
+    };
+
+    let mut vars = Vec::new();
+    while let Some(var) = parser.next_var()? {
+        vars.push(var);
+    }
+    Ok(vars)
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+    /// Line number of the start of `rest`, used in errors.
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: impl std::fmt::Display) -> Error {
+        Error::new(ErrorKind::Syntax, format_args!("line {}: {msg}", self.line))
+    }
+
+    /// Consume `n` bytes of the rest.
+    fn advance(&mut self, n: usize) {
+        self.line += self.rest[..n].matches('\n').count();
+        self.rest = &self.rest[n..];
+    }
+
+    /// Consume the rest of the current line, including the newline.
+    fn skip_line(&mut self) {
+        let n = self.rest.find('\n').map_or(self.rest.len(), |n| n + 1);
+        self.advance(n);
+    }
+
+    fn skip_inline_whitespace(&mut self) {
+        let n = self.rest.len() - self.rest.trim_start_matches([' ', '\t']).len();
+        self.advance(n);
+    }
+
+    fn next_var(&mut self) -> Result<Option<(String, String)>, Error> {
+        loop {
+            self.skip_inline_whitespace();
+            match self.rest.chars().next() {
+                None => return Ok(None),
+                Some('\n' | '\r' | '#') => self.skip_line(),
+                Some(_) => break,
+            }
+        }
+
+        if let Some(rest) = self.rest.strip_prefix("export") {
+            if rest.starts_with([' ', '\t']) {
+                self.advance("export".len());
+                self.skip_inline_whitespace();
+            }
+        }
+
+        let key_len = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')))
+            .unwrap_or(self.rest.len());
+        let key = self.rest[..key_len].to_string();
+        if key.is_empty() {
+            return Err(self.error("expected env name"));
+        }
+        self.advance(key_len);
+
+        self.skip_inline_whitespace();
+        if !self.rest.starts_with('=') {
+            return Err(self.error(format_args!("expected `=` after `{key}`")));
+        }
+        self.advance(1);
+        self.skip_inline_whitespace();
+
+        let value = match self.rest.chars().next() {
+            Some('\'') => self.single_quoted()?,
+            Some('"') => self.double_quoted()?,
+            _ => self.unquoted(),
+        };
+        Ok(Some((key, value)))
+    }
+
+    fn unquoted(&mut self) -> String {
+        let end = self.rest.find('\n').unwrap_or(self.rest.len());
+        let line = &self.rest[..end];
+
+        // `#` only starts a comment after whitespace, so `a#b` is kept.
+        let value = line
+            .match_indices('#')
+            .find(|(idx, _)| *idx == 0 || line[..*idx].ends_with([' ', '\t']))
+            .map_or(line, |(idx, _)| &line[..idx]);
+        let value = value.trim().to_string();
+
+        self.skip_line();
+        value
+    }
+
+    fn single_quoted(&mut self) -> Result<String, Error> {
+        let end = self.rest[1..]
+            .find('\'')
+            .ok_or_else(|| self.error("unclosed single quote"))?;
+        let value = self.rest[1..end + 1].to_string();
+
+        self.advance(end + 2);
+        self.end_of_quoted()?;
+        Ok(value)
+    }
+
+    fn double_quoted(&mut self) -> Result<String, Error> {
+        let mut value = String::new();
+        let mut chars = self.rest.char_indices().skip(1);
+        let end = loop {
+            match chars.next() {
+                None => return Err(self.error("unclosed double quote")),
+                Some((idx, '"')) => break idx,
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, c @ ('"' | '\\' | '$'))) => value.push(c),
+                    // Unknown escapes are kept as is.
+                    Some((_, c)) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => return Err(self.error("unclosed double quote")),
+                },
+                Some((_, c)) => value.push(c),
+            }
+        };
+
+        self.advance(end + 1);
+        self.end_of_quoted()?;
+        Ok(value)
+    }
+
+    /// Only whitespace and comment are allowed after quoted value.
+    fn end_of_quoted(&mut self) -> Result<(), Error> {
+        self.skip_inline_whitespace();
+        match self.rest.chars().next() {
+            None | Some('\n' | '\r' | '#') => {
+                self.skip_line();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format_args!("unexpected `{c}` after quoted value"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(content: &str) -> Vec<(String, String)> {
+        parse(content).expect("must succeed")
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let content = r#"
+# comment
+A=1
+  export B = two words  # comment
+C=a#b
+D=
+E='single "quoted" \n $x'  # comment
+F="double 'quoted' \"\\\$x\n\tend"
+G="multi
+line"
+H='multi
+line'
+exported=1
+"#;
+        assert_eq!(
+            parse_ok(content),
+            vars(&[
+                ("A", "1"),
+                ("B", "two words"),
+                ("C", "a#b"),
+                ("D", ""),
+                ("E", r#"single "quoted" \n $x"#),
+                ("F", "double 'quoted' \"\\$x\n\tend"),
+                ("G", "multi\nline"),
+                ("H", "multi\nline"),
+                ("exported", "1"),
+            ])
+        );
+
+        assert_eq!(parse_ok("A=1\r\nB=2"), vars(&[("A", "1"), ("B", "2")]));
+        assert_eq!(parse_ok(""), vars(&[]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse("A=1\nB\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Syntax);
+        assert_eq!(err.to_string(), "line 2: expected `=` after `B`");
+
+        let err = parse("A=1\nB=\"x\n\ny").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: unclosed double quote");
+
+        let err = parse("A='x'y").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: unexpected `y` after quoted value");
+
+        let err = parse("=x").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: expected env name");
+    }
+}
@@ -1,36 +1,352 @@
 use std::fmt::{self, Debug, Display};
+use std::path::Path;
 
 use serde::{de, ser};
 
+use crate::secret::REDACTED;
+
 /// Errors returned by serde-env.
+///
+/// Besides the message, it carries the full name of the env that caused
+/// the error (including the prefix), the raw value and the expected type
+/// when they are known.
+#[derive(Debug)]
+pub struct Error(Box<Inner>);
+
 #[derive(Debug)]
-pub struct Error(String);
+struct Inner {
+    kind: ErrorKind,
+    message: String,
+    /// Message without the raw value, used if the env is secret. `None` if
+    /// the message is known not to show it.
+    redacted_message: Option<String>,
+    key: Option<String>,
+    field: Option<String>,
+    value: Option<String>,
+    expected: Option<String>,
+    source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    errors: Vec<Error>,
+}
+
+/// Kinds of [`Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A required field is not found in env.
+    MissingField,
+    /// A field is found in env but not expected.
+    UnknownField,
+    /// Value doesn't match any variant of the enum.
+    UnknownVariant,
+    /// Value can't be parsed into the expected type.
+    Parse,
+    /// Value has a different type than expected.
+    InvalidType,
+    /// Value has the right type but is not expected.
+    InvalidValue,
+    /// Sequence or tuple has a different length than expected.
+    InvalidLength,
+    /// Reading a file failed.
+    Io,
+    /// A `.env` file has invalid syntax.
+    Syntax,
+    /// A reference to other env in a value can't be expanded under
+    /// [`EnvDeserializer::interpolate`](crate::EnvDeserializer::interpolate).
+    Interpolation,
+    /// Several errors collected under
+    /// [`EnvDeserializer::collect_errors`](crate::EnvDeserializer::collect_errors),
+    /// see [`Error::errors`].
+    Multiple,
+    /// Other errors, like invalid options or errors from custom
+    /// deserializers.
+    Custom,
+}
 
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error(msg.to_string())
+        Error::new(ErrorKind::Custom, msg)
     }
 }
 
 impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error(msg.to_string())
+        Error::new(ErrorKind::Custom, msg)
+    }
+
+    fn invalid_type(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        let mut err = Error::new(
+            ErrorKind::InvalidType,
+            format_args!("invalid type: {unexp}, expected {exp}"),
+        );
+        if let Some((value, redacted)) = unexpected_value(unexp) {
+            err.0.value = Some(value);
+            err.0.redacted_message = Some(format!("invalid type: {redacted}, expected {exp}"));
+        }
+        err.0.expected = Some(exp.to_string());
+        err
+    }
+
+    fn invalid_value(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        let mut err = Error::new(
+            ErrorKind::InvalidValue,
+            format_args!("invalid value: {unexp}, expected {exp}"),
+        );
+        if let Some((_, redacted)) = unexpected_value(unexp) {
+            err.0.redacted_message = Some(format!("invalid value: {redacted}, expected {exp}"));
+        }
+        err.0.expected = Some(exp.to_string());
+        err
+    }
+
+    fn invalid_length(len: usize, exp: &dyn de::Expected) -> Self {
+        let mut err = Error::new(
+            ErrorKind::InvalidLength,
+            format_args!("invalid length {len}, expected {exp}"),
+        );
+        err.0.expected = Some(exp.to_string());
+        err
+    }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        let mut err = Error::new(
+            ErrorKind::UnknownVariant,
+            format_args!("unknown variant `{variant}`, expected {}", OneOf(expected)),
+        );
+        err.0.value = Some(variant.to_string());
+        err.0.redacted_message = Some(format!(
+            "unknown variant `{REDACTED}`, expected {}",
+            OneOf(expected)
+        ));
+        err.0.expected = Some(OneOf(expected).to_string());
+        err
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        let mut err = Error::new(
+            ErrorKind::UnknownField,
+            format_args!("unknown field `{field}`, expected {}", OneOf(expected)),
+        );
+        err.0.field = Some(field.to_string());
+        err
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        let mut err = Error::new(
+            ErrorKind::MissingField,
+            format_args!("missing field `{field}`"),
+        );
+        err.0.field = Some(field.to_string());
+        err
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.0, f)
+        if let Some(key) = &self.0.key {
+            write!(f, "env `{key}`: ")?;
+        }
+        match (self.0.kind, &self.0.value, &self.0.expected) {
+            (ErrorKind::Parse, value, Some(expected)) => {
+                let value = value.as_deref().unwrap_or(REDACTED);
+                write!(f, "failed to parse `{value}` as {expected}: ")?;
+                Display::fmt(&self.0.message, f)
+            }
+            (ErrorKind::Multiple, _, _) => {
+                Display::fmt(&self.0.message, f)?;
+                for err in &self.0.errors {
+                    write!(f, "\n  {err}")?;
+                }
+                Ok(())
+            }
+            _ => Display::fmt(&self.0.message, f),
+        }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0
+            .source
+            .as_deref()
+            .map(|err| err as &(dyn std::error::Error + 'static))
+    }
+}
 
 impl Error {
-    pub(crate) fn new<E>(err: E) -> Self
+    pub(crate) fn new(kind: ErrorKind, message: impl Display) -> Self {
+        Self(Box::new(Inner {
+            kind,
+            message: message.to_string(),
+            redacted_message: None,
+            key: None,
+            field: None,
+            value: None,
+            expected: None,
+            source: None,
+            errors: Vec::new(),
+        }))
+    }
+
+    /// Create a parse error for `value` which is expected to be `expected`.
+    ///
+    /// The value of a `secret` env is left out, including the message of
+    /// `source` if it shows the value.
+    pub(crate) fn parse<E>(value: &str, expected: &str, source: E, secret: bool) -> Self
     where
-        E: std::error::Error,
+        E: std::error::Error + Send + Sync + 'static,
     {
-        Self(err.to_string())
+        let message = source.to_string();
+        let mut err = if !secret {
+            let mut err = Error::new(ErrorKind::Parse, message);
+            err.0.value = Some(value.to_string());
+            err
+        } else if value.is_empty() || !message.contains(value) {
+            Error::new(ErrorKind::Parse, message)
+        } else {
+            Error::new(ErrorKind::Parse, "invalid value")
+        };
+        err.0.expected = Some(expected.to_string());
+        err.0.source = Some(Box::new(source));
+        err
+    }
+
+    /// Create an error for failing to read the file at `path`.
+    pub(crate) fn io(path: &Path, source: std::io::Error) -> Self {
+        let mut err = Error::new(
+            ErrorKind::Io,
+            format_args!("failed to read `{}`: {source}", path.display()),
+        );
+        err.0.source = Some(Box::new(source));
+        err
+    }
+
+    /// Combine collected errors, or return the only one as is.
+    pub(crate) fn multiple(mut errors: Vec<Error>) -> Self {
+        if errors.len() == 1 {
+            return errors.remove(0);
+        }
+        let mut err = Error::new(
+            ErrorKind::Multiple,
+            format_args!("found {} errors:", errors.len()),
+        );
+        err.0.errors = errors;
+        err
+    }
+
+    /// Set the env key and value that caused the error if they are not set
+    /// yet, so that the innermost one wins.
+    pub(crate) fn with_env(mut self, key: String, value: Option<&str>) -> Self {
+        if self.0.key.is_none() {
+            self.0.key = Some(key);
+            if self.0.value.is_none() {
+                self.0.value = value.map(|v| v.to_string());
+            }
+        }
+        self
+    }
+
+    /// Set the message to show instead if the env turns out to be secret,
+    /// for errors whose message shows the raw value.
+    pub(crate) fn with_redacted_message(mut self, message: impl Display) -> Self {
+        self.0.redacted_message = Some(message.to_string());
+        self
+    }
+
+    /// Hide the value of a secret env, including `raw` which is the value
+    /// known by the caller, from the error.
+    ///
+    /// Messages from custom deserializers may show the value in any form,
+    /// so they are replaced as a whole if they contain it.
+    pub(crate) fn redact(mut self, raw: Option<&str>) -> Self {
+        let value = self.0.value.take();
+        if let Some(message) = self.0.redacted_message.take() {
+            self.0.message = message;
+        } else if self.0.kind == ErrorKind::Custom
+            && raw
+                .into_iter()
+                .chain(value.as_deref())
+                .any(|v| !v.is_empty() && self.0.message.contains(v))
+        {
+            self.0.message = "invalid value".to_string();
+        }
+        self
+    }
+
+    /// Append a hint about the likely cause to the message.
+    pub(crate) fn with_hint(mut self, hint: impl Display) -> Self {
+        self.0.message = format!("{} ({hint})", self.0.message);
+        self
+    }
+
+    /// Get the kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.0.kind
+    }
+
+    /// Get the full name of the env that caused this error, including the
+    /// prefix, like `APP_DB_PORT`.
+    ///
+    /// For [`ErrorKind::MissingField`], it's the name of the env that is
+    /// expected to be set.
+    pub fn key(&self) -> Option<&str> {
+        self.0.key.as_deref()
+    }
+
+    /// Get the name of the field that is missing or unknown.
+    pub fn field(&self) -> Option<&str> {
+        self.0.field.as_deref()
+    }
+
+    /// Get the raw value of the env that caused this error.
+    ///
+    /// It's always `None` for secret env, see
+    /// [`EnvDeserializer::secret_patterns`](crate::EnvDeserializer::secret_patterns).
+    pub fn value(&self) -> Option<&str> {
+        self.0.value.as_deref()
+    }
+
+    /// Get the description of the expected type or value.
+    pub fn expected(&self) -> Option<&str> {
+        self.0.expected.as_deref()
+    }
+
+    /// Get the errors combined in an [`ErrorKind::Multiple`] error, empty
+    /// for other errors.
+    pub fn errors(&self) -> &[Error] {
+        &self.0.errors
+    }
+}
+
+/// Get the raw value shown by `unexp`, and how `unexp` is shown without it.
+fn unexpected_value(unexp: de::Unexpected) -> Option<(String, String)> {
+    let (value, kind) = match unexp {
+        de::Unexpected::Str(v) => (v.to_string(), "string"),
+        de::Unexpected::Bool(v) => (v.to_string(), "boolean"),
+        de::Unexpected::Unsigned(v) => (v.to_string(), "integer"),
+        de::Unexpected::Signed(v) => (v.to_string(), "integer"),
+        de::Unexpected::Float(v) => (v.to_string(), "floating point"),
+        de::Unexpected::Char(v) => (v.to_string(), "character"),
+        _ => return None,
+    };
+    Some((value, format!("{kind} `{REDACTED}`")))
+}
+
+/// Format expected names like serde does: `a`, `a` or `b`, `a`, `b` or `c`.
+struct OneOf(&'static [&'static str]);
+
+impl Display for OneOf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            [] => write!(f, "there are no variants"),
+            [a] => write!(f, "`{a}`"),
+            [a, b] => write!(f, "`{a}` or `{b}`"),
+            [init @ .., last] => {
+                f.write_str("one of ")?;
+                for v in init {
+                    write!(f, "`{v}`, ")?;
+                }
+                write!(f, "`{last}`")
+            }
+        }
     }
 }
@@ -0,0 +1,241 @@
+use std::fmt::Write;
+
+use crate::error::{Error, ErrorKind};
+
+/// Format to export env into, see [`export`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExportFormat {
+    /// POSIX shell script like `export KEY='value'`.
+    Shell,
+    /// Docker `--env-file` like `KEY=value`, which can't contain newlines
+    /// as docker doesn't support quoting.
+    DockerEnvFile,
+    /// systemd `EnvironmentFile` like `KEY="value"`.
+    Systemd,
+    /// Kubernetes container `env:` YAML fragment.
+    KubernetesEnv,
+    /// Kubernetes ConfigMap `data:` YAML fragment.
+    KubernetesConfigMap,
+}
+
+/// Render env like the output of [`to_env`](crate::to_env) in `format`.
+///
+/// Values are quoted for the format, so they are read back as is. Names
+/// or values that the format can't represent return an error naming the
+/// env.
+///
+/// # Examples
+///
+/// ```
+/// use serde_env::{export, ExportFormat};
+///
+/// let vars = [("GREETING", "it's \"fine\"\nreally")];
+///
+/// assert_eq!(
+///     export(vars, ExportFormat::Shell).expect("export env"),
+///     "export GREETING='it'\\''s \"fine\"\nreally'\n"
+/// );
+/// assert_eq!(
+///     export(vars, ExportFormat::Systemd).expect("export env"),
+///     "GREETING=\"it's \\\"fine\\\"\nreally\"\n"
+/// );
+/// assert_eq!(
+///     export(vars, ExportFormat::KubernetesEnv).expect("export env"),
+///     "env:\n  - name: \"GREETING\"\n    value: \"it's \\\"fine\\\"\\nreally\"\n"
+/// );
+/// assert!(export(vars, ExportFormat::DockerEnvFile).is_err());
+/// ```
+pub fn export<I, K, V>(vars: I, format: ExportFormat) -> Result<String, Error>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut out = String::new();
+    match format {
+        ExportFormat::KubernetesEnv => out.push_str("env:\n"),
+        ExportFormat::KubernetesConfigMap => out.push_str("data:\n"),
+        _ => {}
+    }
+
+    for (k, v) in vars {
+        let (k, v) = (k.as_ref(), v.as_ref());
+        check_name(k, format)?;
+
+        // Writing into string never fails.
+        let _ = match format {
+            ExportFormat::Shell => writeln!(out, "export {k}={}", shell_quote(v)),
+            ExportFormat::DockerEnvFile => {
+                if v.contains(['\n', '\r']) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidValue,
+                        "value with newlines can't be written into docker env file",
+                    )
+                    .with_env(k.to_string(), None));
+                }
+                writeln!(out, "{k}={v}")
+            }
+            ExportFormat::Systemd => writeln!(out, "{k}={}", systemd_quote(v)),
+            ExportFormat::KubernetesEnv => {
+                writeln!(
+                    out,
+                    "  - name: {}\n    value: {}",
+                    yaml_quote(k),
+                    yaml_quote(v)
+                )
+            }
+            ExportFormat::KubernetesConfigMap => {
+                writeln!(out, "  {}: {}", yaml_quote(k), yaml_quote(v))
+            }
+        };
+    }
+
+    Ok(out)
+}
+
+/// Check that name can be written in format without quoting.
+fn check_name(name: &str, format: ExportFormat) -> Result<(), Error> {
+    let valid = match format {
+        // Shell and systemd only accept identifiers.
+        ExportFormat::Shell | ExportFormat::Systemd => {
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        ExportFormat::DockerEnvFile => {
+            !name.is_empty()
+                && !name.starts_with('#')
+                && !name.contains(|c: char| c == '=' || c.is_whitespace())
+        }
+        ExportFormat::KubernetesEnv | ExportFormat::KubernetesConfigMap => {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidValue,
+            format_args!("name is not valid for {format:?}"),
+        )
+        .with_env(name.to_string(), None))
+    }
+}
+
+/// Quote value in single quotes, where nothing is special but the quote
+/// itself.
+fn shell_quote(v: &str) -> String {
+    format!("'{}'", v.replace('\'', r"'\''"))
+}
+
+/// Quote value in double quotes, escaping the characters systemd treats as
+/// special inside.
+fn systemd_quote(v: &str) -> String {
+    let mut s = String::with_capacity(v.len() + 2);
+    s.push('"');
+    for c in v.chars() {
+        if matches!(c, '"' | '\\' | '`' | '$') {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s.push('"');
+    s
+}
+
+/// Quote value as a YAML double-quoted scalar.
+fn yaml_quote(v: &str) -> String {
+    let mut s = String::with_capacity(v.len() + 2);
+    s.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => s.push_str(r#"\""#),
+            '\\' => s.push_str(r"\\"),
+            '\n' => s.push_str(r"\n"),
+            '\r' => s.push_str(r"\r"),
+            '\t' => s.push_str(r"\t"),
+            c if c.is_control() => {
+                let _ = write!(s, "\\u{:04x}", c as u32);
+            }
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARS: [(&str, &str); 3] = [
+        ("APP_NAME", "app"),
+        ("APP_MOTD", "it's $HOME\n\t\"ok\" \\"),
+        ("APP_EMPTY", ""),
+    ];
+
+    #[test]
+    fn test_export() {
+        assert_eq!(
+            export(VARS, ExportFormat::Shell).expect("must succeed"),
+            "export APP_NAME='app'\n\
+             export APP_MOTD='it'\\''s $HOME\n\t\"ok\" \\'\n\
+             export APP_EMPTY=''\n"
+        );
+        assert_eq!(
+            export(VARS, ExportFormat::Systemd).expect("must succeed"),
+            "APP_NAME=\"app\"\n\
+             APP_MOTD=\"it's \\$HOME\n\t\\\"ok\\\" \\\\\"\n\
+             APP_EMPTY=\"\"\n"
+        );
+        assert_eq!(
+            export(VARS, ExportFormat::KubernetesEnv).expect("must succeed"),
+            "env:\n  \
+               - name: \"APP_NAME\"\n    value: \"app\"\n  \
+               - name: \"APP_MOTD\"\n    value: \"it's $HOME\\n\\t\\\"ok\\\" \\\\\"\n  \
+               - name: \"APP_EMPTY\"\n    value: \"\"\n"
+        );
+        assert_eq!(
+            export(VARS, ExportFormat::KubernetesConfigMap).expect("must succeed"),
+            "data:\n  \
+               \"APP_NAME\": \"app\"\n  \
+               \"APP_MOTD\": \"it's $HOME\\n\\t\\\"ok\\\" \\\\\"\n  \
+               \"APP_EMPTY\": \"\"\n"
+        );
+        assert_eq!(
+            export([VARS[0], VARS[2]], ExportFormat::DockerEnvFile).expect("must succeed"),
+            "APP_NAME=app\nAPP_EMPTY=\n"
+        );
+
+        // Names YAML 1.1 reads as booleans stay strings.
+        assert_eq!(
+            export([("ON", "1")], ExportFormat::KubernetesEnv).expect("must succeed"),
+            "env:\n  - name: \"ON\"\n    value: \"1\"\n"
+        );
+        assert_eq!(
+            export([("ON", "1")], ExportFormat::KubernetesConfigMap).expect("must succeed"),
+            "data:\n  \"ON\": \"1\"\n"
+        );
+    }
+
+    #[test]
+    fn test_export_errors() {
+        let err = export(VARS, ExportFormat::DockerEnvFile).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `APP_MOTD`: value with newlines can't be written into docker env file"
+        );
+
+        let err = export([("APP-NAME", "app")], ExportFormat::Shell).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `APP-NAME`: name is not valid for Shell"
+        );
+        assert!(export([("APP-NAME", "app")], ExportFormat::KubernetesEnv).is_ok());
+        assert!(export([("1APP", "app")], ExportFormat::Systemd).is_err());
+    }
+}
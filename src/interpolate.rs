@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use crate::error::{Error, ErrorKind};
+use crate::value::Value;
+
+/// Expander of references to other env in values.
+///
+/// Supported syntax:
+///
+/// - `$NAME` and `${NAME}`: value of `NAME`, which must be set.
+/// - `${NAME:-default}`: value of `NAME`, or `default` if it's unset or
+///   empty.
+/// - `${NAME:?message}`: value of `NAME`, or an error with `message` if it's
+///   unset or empty.
+/// - `$$`: a literal `$`.
+///
+/// References are resolved against all `vars`, and expanded recursively.
+/// Values are only expanded when asked for, so env that nothing reads can
+/// contain anything.
+#[derive(Debug)]
+pub(crate) struct Expander {
+    vars: Vec<(String, Value)>,
+    index: HashMap<String, usize>,
+    /// Expanded values by index.
+    expanded: HashMap<usize, String>,
+    /// Indexes of env being expanded, used to detect cycles.
+    stack: Vec<usize>,
+}
+
+impl Expander {
+    pub(crate) fn new(vars: Vec<(String, Value)>) -> Self {
+        Self {
+            // Later env overrides earlier one with the same key.
+            index: vars
+                .iter()
+                .enumerate()
+                .map(|(idx, (k, _))| (k.clone(), idx))
+                .collect(),
+            vars,
+            expanded: HashMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Expand references in `value` which is the value of env `key`.
+    pub(crate) fn expand(&mut self, key: &str, value: &str) -> Result<String, Error> {
+        // Go through the env itself if it's in `vars`, so a reference back to
+        // it is reported as a cycle starting from it.
+        match self.index.get(key) {
+            Some(&idx) if self.vars[idx].1.as_str() == Some(value) => self.value(idx),
+            _ => self.expand_str(key, value),
+        }
+    }
+
+    /// Get the expanded value of env at `idx`.
+    fn value(&mut self, idx: usize) -> Result<String, Error> {
+        if let Some(v) = self.expanded.get(&idx) {
+            return Ok(v.clone());
+        }
+
+        let (key, value) = self.vars[idx].clone();
+        if let Some(start) = self.stack.iter().position(|i| *i == idx) {
+            let cycle: Vec<_> = self.stack[start..]
+                .iter()
+                .chain([&idx])
+                .map(|i| self.vars[*i].0.as_str())
+                .collect();
+            return Err(interpolation_error(
+                &key,
+                format_args!("cycle in references: {}", cycle.join(" -> ")),
+            ));
+        }
+        let value = value.as_str().ok_or_else(|| {
+            interpolation_error(&key, "value is not valid unicode, can't be referenced")
+        })?;
+
+        self.stack.push(idx);
+        let expanded = self.expand_str(&key, value);
+        self.stack.pop();
+
+        let expanded = expanded?;
+        self.expanded.insert(idx, expanded.clone());
+        Ok(expanded)
+    }
+
+    /// Get the expanded value of env `name`, `None` if it's not set.
+    fn lookup(&mut self, name: &str) -> Result<Option<String>, Error> {
+        match self.index.get(name) {
+            None => Ok(None),
+            Some(idx) => self.value(*idx).map(Some),
+        }
+    }
+
+    /// Expand references in `s` which is (part of) the value of env `key`.
+    fn expand_str(&mut self, key: &str, s: &str) -> Result<String, Error> {
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+
+        while let Some(pos) = rest.find('$') {
+            out.push_str(&rest[..pos]);
+            rest = &rest[pos + 1..];
+
+            if let Some(remain) = rest.strip_prefix('$') {
+                out.push('$');
+                rest = remain;
+            } else if let Some(remain) = rest.strip_prefix('{') {
+                let end = closing_brace(remain)
+                    .ok_or_else(|| interpolation_error(key, "unclosed `${` in value"))?;
+                out.push_str(&self.expand_braced(key, &remain[..end])?);
+                rest = &remain[end + 1..];
+            } else {
+                let len = name_len(rest);
+                if len == 0 {
+                    // Not a reference, keep `$` as is.
+                    out.push('$');
+                    continue;
+                }
+                out.push_str(&self.required(key, &rest[..len])?);
+                rest = &rest[len..];
+            }
+        }
+
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    /// Expand the content of `${...}`.
+    fn expand_braced(&mut self, key: &str, expr: &str) -> Result<String, Error> {
+        let len = name_len(expr);
+        let (name, op) = expr.split_at(len);
+        if name.is_empty() {
+            return Err(interpolation_error(
+                key,
+                format_args!("invalid reference `${{{expr}}}`"),
+            ));
+        }
+
+        if op.is_empty() {
+            self.required(key, name)
+        } else if let Some(default) = op.strip_prefix(":-") {
+            match self.lookup(name)? {
+                Some(v) if !v.is_empty() => Ok(v),
+                _ => self.expand_str(key, default),
+            }
+        } else if let Some(message) = op.strip_prefix(":?") {
+            match self.lookup(name)? {
+                Some(v) if !v.is_empty() => Ok(v),
+                _ => {
+                    let message = self.expand_str(key, message)?;
+                    Err(interpolation_error(
+                        key,
+                        format_args!("referenced env `{name}` is not set: {message}"),
+                    ))
+                }
+            }
+        } else {
+            Err(interpolation_error(
+                key,
+                format_args!("unsupported reference `${{{expr}}}`"),
+            ))
+        }
+    }
+
+    /// Get the expanded value of env `name` which must be set.
+    fn required(&mut self, key: &str, name: &str) -> Result<String, Error> {
+        self.lookup(name)?.ok_or_else(|| {
+            interpolation_error(key, format_args!("referenced env `{name}` is not set"))
+        })
+    }
+}
+
+fn interpolation_error(key: &str, msg: impl std::fmt::Display) -> Error {
+    Error::new(ErrorKind::Interpolation, msg).with_env(key.to_string(), None)
+}
+
+/// Length of the env name at the start of `s`.
+fn name_len(s: &str) -> usize {
+    if !s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return 0;
+    }
+    s.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(s.len())
+}
+
+/// Find the `}` closing `${`, skipping nested `${...}`.
+fn closing_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut chars = s.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '$' if chars.next_if(|(_, c)| *c == '{').is_some() => depth += 1,
+            '}' if depth == 0 => return Some(idx),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_all(vars: &[(&str, &str)]) -> Result<Vec<(String, String)>, Error> {
+        let mut expander = Expander::new(
+            vars.iter()
+                .map(|(k, v)| (k.to_string(), Value::from(*v)))
+                .collect(),
+        );
+        vars.iter()
+            .map(|(k, v)| Ok((k.to_string(), expander.expand(k, v)?)))
+            .collect()
+    }
+
+    fn expand_ok(vars: &[(&str, &str)]) -> Vec<String> {
+        expand_all(vars)
+            .expect("must succeed")
+            .into_iter()
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(
+            expand_ok(&[
+                ("URL", "postgres://${USER}@$HOST/app"),
+                ("USER", "admin"),
+                ("HOST", "${DB_HOST:-localhost}:${DB_PORT:-5432}"),
+                ("DB_PORT", "6432"),
+                ("PRICE", "$$5 $ 1$"),
+                ("EMPTY", ""),
+                ("NESTED", "${EMPTY:-${USER:?user is required}}"),
+            ]),
+            vec![
+                "postgres://admin@localhost:6432/app",
+                "admin",
+                "localhost:6432",
+                "6432",
+                "$5 $ 1$",
+                "",
+                "admin",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let err = expand_all(&[("URL", "${HOST}/app")]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Interpolation);
+        assert_eq!(err.key(), Some("URL"));
+        assert_eq!(
+            err.to_string(),
+            "env `URL`: referenced env `HOST` is not set"
+        );
+
+        let err =
+            expand_all(&[("URL", "$HOST"), ("HOST", "${PORT:?port is required}")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `HOST`: referenced env `PORT` is not set: port is required"
+        );
+
+        let err = expand_all(&[("A", "${B}"), ("B", "x${C}"), ("C", "$A")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `A`: cycle in references: A -> B -> C -> A"
+        );
+
+        let err = expand_all(&[("A", "${B")]).unwrap_err();
+        assert_eq!(err.to_string(), "env `A`: unclosed `${` in value");
+
+        let err = expand_all(&[("A", "${B:+x}"), ("B", "1")]).unwrap_err();
+        assert_eq!(err.to_string(), "env `A`: unsupported reference `${B:+x}`");
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+/// Sources of env merged in order, see
+/// [`EnvDeserializer::from_layers`](crate::EnvDeserializer::from_layers).
+///
+/// Later layers override earlier ones key by key, so setting
+/// `APP_DB_HOST` in a later layer keeps `APP_DB_PORT` from an earlier one.
+/// Sources are read when deserializing, not when added.
+///
+/// # Examples
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_env::{to_env_with_prefix, EnvDeserializer, Layers};
+///
+/// #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+/// struct Db {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// #[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+/// struct Test {
+///     db: Db,
+/// }
+///
+/// let defaults = Test {
+///     db: Db {
+///         host: "localhost".to_string(),
+///         port: 5432,
+///     },
+/// };
+///
+/// let layers = Layers::new()
+///     .vars(to_env_with_prefix(&defaults, "APP").expect("serialize defaults"))
+///     .dotenv_str("APP_DB_HOST=db\nAPP_DB_PORT=6432\n")
+///     .vars([("APP_DB_PORT", "7432")]);
+/// let actual: Test = EnvDeserializer::new()
+///     .prefix("APP")
+///     .from_layers(&layers)
+///     .expect("deserialize from layers");
+///
+/// let expected = Test {
+///     db: Db {
+///         host: "db".to_string(),
+///         port: 7432,
+///     },
+/// };
+///
+/// assert_eq!(actual, expected);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Layers {
+    pub(crate) layers: Vec<Layer>,
+}
+
+/// A source of env in [`Layers`].
+#[derive(Debug, Clone)]
+pub(crate) enum Layer {
+    Vars(Vec<(String, String)>),
+    DotenvStr(String),
+    DotenvFile(PathBuf),
+    Env,
+}
+
+impl Layers {
+    /// Create layers without any source.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add env from an iterable of keys and values, like defaults from
+    /// [`to_env`](crate::to_env) or explicit overrides.
+    pub fn vars<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let vars = vars
+            .into_iter()
+            .map(|(k, v)| (k.as_ref().to_string(), v.as_ref().to_string()))
+            .collect();
+        self.layers.push(Layer::Vars(vars));
+        self
+    }
+
+    /// Add env from the content of a `.env` file.
+    pub fn dotenv_str(mut self, content: impl Into<String>) -> Self {
+        self.layers.push(Layer::DotenvStr(content.into()));
+        self
+    }
+
+    /// Add env from a `.env` file, which must exist.
+    pub fn dotenv_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.layers.push(Layer::DotenvFile(path.into()));
+        self
+    }
+
+    /// Add the process env.
+    pub fn env(mut self) -> Self {
+        self.layers.push(Layer::Env);
+        self
+    }
+}
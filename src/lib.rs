@@ -4,6 +4,16 @@
 //! serde-env supports deserialize `_` separated env into nests structs. That means
 //! we will treat env as `_` separated tree instead of a flat map.
 //!
+//! The separator can be changed with [`EnvDeserializer::separator`], for
+//! example `__` keeps `APP__DB__MAX_CONNS` as `db` => `max_conns`.
+//!
+//! Structs can be serialized back into env with [`to_env`] as well, which
+//! `from_iter` reads as is, and rendered as a shell script, docker env file,
+//! systemd env file or Kubernetes YAML with [`export`].
+//!
+//! Defaults, `.env` files, the process env and overrides can be merged with
+//! [`Layers`] and deserialized at once.
+//!
 //! For examples:
 //!
 //! ```
@@ -33,8 +43,23 @@
 //! ```
 
 mod de;
+mod dotenv;
 mod error;
+mod export;
+mod interpolate;
+mod layers;
+mod list;
+mod secret;
+mod ser;
+mod suggest;
 mod value;
 
-pub use de::{from_env, from_env_with_prefix, from_iter, from_iter_with_prefix};
-pub use error::Error;
+pub use de::{
+    from_dotenv_file, from_dotenv_str, from_env, from_env_with_prefix, from_iter,
+    from_iter_with_prefix, EmptyValues, EnvDeserializer,
+};
+pub use error::{Error, ErrorKind};
+pub use export::{export, ExportFormat};
+pub use layers::Layers;
+pub use list::ListEscape;
+pub use ser::{to_env, to_env_with_prefix};
@@ -0,0 +1,214 @@
+use crate::error::{Error, ErrorKind};
+use crate::secret::REDACTED;
+
+/// How a literal list delimiter can be escaped inside an element.
+///
+/// - `None`: No escaping, every delimiter splits the value.
+/// - `Backslash`: `\,` is a literal `,` and `\\` is a literal `\`.
+/// - `Quote`: CSV-style quoting, `"a,b",c` => `a,b` and `c`, `""` inside quotes is a literal `"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListEscape {
+    /// No escaping.
+    #[default]
+    None,
+    /// Escape with backslash.
+    Backslash,
+    /// Escape with CSV-style double quotes.
+    Quote,
+}
+
+/// Split value into list elements.
+///
+/// Whitespace around elements is trimmed before unescaping if `trim` is
+/// set, so escaped or quoted whitespace is kept. Elements that are empty
+/// before unescaping are dropped unless `keep_empty` is set, which means
+/// `""` with `ListEscape::Quote` is always kept as an empty element.
+pub(crate) fn split(
+    value: &str,
+    delimiter: &str,
+    escape: ListEscape,
+    trim: bool,
+    keep_empty: bool,
+) -> Result<Vec<String>, Error> {
+    let mut elements = Vec::new();
+
+    for raw in split_raw(value, delimiter, escape)? {
+        let raw = if trim { trim_raw(raw, escape) } else { raw };
+        if raw.is_empty() && !keep_empty {
+            continue;
+        }
+        elements.push(unescape(raw, escape));
+    }
+
+    Ok(elements)
+}
+
+/// Split value by delimiter which is not escaped or quoted.
+///
+/// A delimiter made of whitespace only splits on any run of whitespace.
+fn split_raw<'a>(
+    value: &'a str,
+    delimiter: &str,
+    escape: ListEscape,
+) -> Result<Vec<&'a str>, Error> {
+    let whitespace = delimiter.trim().is_empty();
+
+    let mut raws = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+
+    let mut chars = value.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        match (escape, c) {
+            (ListEscape::Backslash, '\\') => {
+                chars.next();
+                continue;
+            }
+            (ListEscape::Quote, '"') => {
+                quoted = !quoted;
+                continue;
+            }
+            _ => {}
+        }
+        if quoted {
+            continue;
+        }
+
+        if whitespace && c.is_whitespace() {
+            raws.push(&value[start..idx]);
+            start = idx + c.len_utf8();
+            while let Some((idx, c)) = chars.next_if(|(_, c)| c.is_whitespace()) {
+                start = idx + c.len_utf8();
+            }
+        } else if !whitespace && value[idx..].starts_with(delimiter) {
+            raws.push(&value[start..idx]);
+            start = idx + delimiter.len();
+            // Skip the rest of the delimiter.
+            for _ in 1..delimiter.chars().count() {
+                chars.next();
+            }
+        }
+    }
+
+    if quoted {
+        return Err(Error::new(
+            ErrorKind::Custom,
+            format_args!("unclosed quote in list: {value}"),
+        )
+        .with_redacted_message(format_args!("unclosed quote in list: {REDACTED}")));
+    }
+
+    raws.push(&value[start..]);
+    Ok(raws)
+}
+
+/// Trim whitespace around raw element, but keep the escaped trailing one.
+fn trim_raw(raw: &str, escape: ListEscape) -> &str {
+    let raw = raw.trim_start();
+    let trimmed = raw.trim_end();
+
+    let backslashes = trimmed.bytes().rev().take_while(|b| *b == b'\\').count();
+    if escape == ListEscape::Backslash && backslashes % 2 == 1 && trimmed.len() < raw.len() {
+        let c = raw[trimmed.len()..]
+            .chars()
+            .next()
+            .expect("must have whitespace");
+        &raw[..trimmed.len() + c.len_utf8()]
+    } else {
+        trimmed
+    }
+}
+
+fn unescape(raw: &str, escape: ListEscape) -> String {
+    match escape {
+        ListEscape::None => raw.to_string(),
+        ListEscape::Backslash => {
+            let mut s = String::with_capacity(raw.len());
+            let mut chars = raw.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => s.extend(chars.next()),
+                    c => s.push(c),
+                }
+            }
+            s
+        }
+        ListEscape::Quote => {
+            let mut s = String::with_capacity(raw.len());
+            let mut quoted = false;
+            let mut chars = raw.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if quoted && chars.peek() == Some(&'"') => {
+                        chars.next();
+                        s.push('"');
+                    }
+                    '"' => quoted = !quoted,
+                    c => s.push(c),
+                }
+            }
+            s
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_ok(value: &str, delimiter: &str, escape: ListEscape, keep_empty: bool) -> Vec<String> {
+        split(value, delimiter, escape, true, keep_empty).expect("must succeed")
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split_ok("a, b,,c ", ",", ListEscape::None, false),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            split_ok("a, b,,c ", ",", ListEscape::None, true),
+            vec!["a", "b", "", "c"]
+        );
+        assert_eq!(
+            split_ok("a  b\tc ", " ", ListEscape::None, false),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            split_ok("a  b\tc", " ", ListEscape::None, true),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            split_ok("a::b::c", "::", ListEscape::None, false),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            split(" a , b ", ",", ListEscape::None, false, false).expect("must succeed"),
+            vec![" a ", " b "]
+        );
+    }
+
+    #[test]
+    fn test_split_backslash() {
+        assert_eq!(
+            split_ok(r"^a\,b$, c\\, d\ ", ",", ListEscape::Backslash, false),
+            vec!["^a,b$", r"c\", "d "]
+        );
+    }
+
+    #[test]
+    fn test_split_quote() {
+        assert_eq!(
+            split_ok(
+                r#""http://a/?x=1,2", b, "say ""hi""", """#,
+                ",",
+                ListEscape::Quote,
+                false
+            ),
+            vec!["http://a/?x=1,2", "b", r#"say "hi""#, ""]
+        );
+
+        let err = split(r#""a,b"#, ",", ListEscape::Quote, true, false).unwrap_err();
+        assert_eq!(err.to_string(), r#"unclosed quote in list: "a,b"#);
+    }
+}
@@ -0,0 +1,60 @@
+/// Text shown in place of secret values.
+pub(crate) const REDACTED: &str = "***";
+
+/// Patterns of env names whose values are secret by default.
+pub(crate) const DEFAULT_PATTERNS: [&str; 3] = ["*PASSWORD*", "*TOKEN*", "*SECRET*"];
+
+/// Check if name matches pattern case insensitively, where `*` matches any
+/// characters including none.
+pub(crate) fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let name: Vec<char> = name.chars().flat_map(char::to_lowercase).collect();
+
+    // Position in pattern and name to retry from after the last `*`.
+    let mut retry = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                retry = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            // Let the last `*` match one more character and try again.
+            _ => match retry {
+                Some((rp, rn)) => {
+                    retry = Some((rp, rn + 1));
+                    p = rp + 1;
+                    n = rn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*PASSWORD*", "APP_DB_PASSWORD"));
+        assert!(matches("*PASSWORD*", "password"));
+        assert!(matches("*password*", "APP_PASSWORD_FILE"));
+        assert!(matches("APP_*_KEY", "APP_API_KEY"));
+        assert!(matches("APP_*_KEY", "app_a_b_key"));
+        assert!(matches("*", ""));
+        assert!(matches("APP_KEY", "APP_KEY"));
+
+        assert!(!matches("*PASSWORD*", "APP_PASS"));
+        assert!(!matches("APP_*_KEY", "APP_KEY"));
+        assert!(!matches("APP_KEY", "APP_KEY_ID"));
+        assert!(!matches("", "APP"));
+    }
+}
@@ -0,0 +1,659 @@
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::error::{Error, ErrorKind};
+use crate::value;
+
+/// Separator used to join keys, the same as the default of deserializing.
+const SEPARATOR: &str = "_";
+
+/// Delimiters used to join sequences for each nesting level, the same as the
+/// default of deserializing.
+const LIST_DELIMITERS: [&str; 2] = [",", ":"];
+
+/// Serialize struct into env.
+///
+/// Env is generated with the same conventions that [`from_iter`](crate::from_iter)
+/// reads with default options:
+///
+/// - Keys of nested structs and maps are uppercased and joined by `_`.
+///   Field names are split into words, so `logLevel` is written as
+///   `LOG_LEVEL`.
+/// - Sequences and tuples are joined by `,`, or `:` for the nested ones.
+///   Sequences of structs or maps are written as indexed env like
+///   `SERVERS_0_HOST`.
+/// - Enums are written as the variant name, with the fields of struct
+///   variants as sub-keys.
+/// - `None` and units are skipped.
+///
+/// Some values are not read back as they are:
+///
+/// - Empty strings and sequences are written as empty values, which are
+///   read back as unset unless
+///   [`EmptyValues::Empty`](crate::EmptyValues::Empty) is used.
+/// - Empty maps are written as nothing, so they are only read back into
+///   fields with `#[serde(default)]`.
+/// - Map keys are uppercased, so they are read back in lowercase. Keys
+///   containing `_` are read back as they are only if the values are not
+///   structs or maps, which would take the rest of the key as theirs.
+///
+/// # Examples
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_env::{from_iter, to_env};
+///
+/// #[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// struct Db {
+///     hosts: Vec<String>,
+///     max_conns: u32,
+/// }
+///
+/// #[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// struct Test {
+///     log_level: String,
+///     db: Db,
+/// }
+///
+/// let t = Test {
+///     log_level: "debug".to_string(),
+///     db: Db {
+///         hosts: vec!["a".to_string(), "b".to_string()],
+///         max_conns: 10,
+///     },
+/// };
+///
+/// let env = to_env(&t).expect("serialize into env");
+/// assert_eq!(
+///     env,
+///     vec![
+///         ("LOG_LEVEL".to_string(), "debug".to_string()),
+///         ("DB_HOSTS".to_string(), "a,b".to_string()),
+///         ("DB_MAX_CONNS".to_string(), "10".to_string()),
+///     ]
+/// );
+///
+/// let actual: Test = from_iter(env).expect("deserialize from iter");
+/// assert_eq!(actual, t);
+/// ```
+pub fn to_env<T>(value: &T) -> Result<Vec<(String, String)>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    serialize(value, "")
+}
+
+/// Serialize struct into env with a prefix.
+///
+/// The prefix is joined with the rest of the key by `_`, see [`to_env`] for
+/// the conventions.
+///
+/// # Examples
+///
+/// ```
+/// use serde::Serialize;
+/// use serde_env::to_env_with_prefix;
+///
+/// #[derive(Debug, Serialize)]
+/// struct Test {
+///     home: String,
+/// }
+///
+/// let env = to_env_with_prefix(&Test { home: "/test".to_string() }, "TEST_ENV")
+///     .expect("serialize into env");
+///
+/// assert_eq!(env, vec![("TEST_ENV_HOME".to_string(), "/test".to_string())]);
+/// ```
+pub fn to_env_with_prefix<T>(value: &T, prefix: &str) -> Result<Vec<(String, String)>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    serialize(value, prefix)
+}
+
+fn serialize<T>(value: &T, prefix: &str) -> Result<Vec<(String, String)>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut vars = Vec::new();
+    match value.serialize(Serializer)? {
+        Env::Map(None, entries) => {
+            for (k, v) in entries {
+                v.flatten(join_key(prefix, &k), &mut vars)?;
+            }
+        }
+        _ => {
+            return Err(ser::Error::custom(
+                "only structs and maps can be serialized into env",
+            ))
+        }
+    }
+    Ok(vars)
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    let key = key.to_uppercase();
+    if prefix.is_empty() {
+        key
+    } else {
+        format!("{prefix}{SEPARATOR}{key}")
+    }
+}
+
+/// Env tree serialized from a value.
+enum Env {
+    /// Nothing to write, like `None`.
+    None,
+    Value(String),
+    List(Vec<Env>),
+    /// Struct or map, with the variant name as value for enum variants.
+    Map(Option<String>, Vec<(String, Env)>),
+}
+
+impl Env {
+    /// Flatten env tree into key value pairs.
+    fn flatten(self, key: String, vars: &mut Vec<(String, String)>) -> Result<(), Error> {
+        match self {
+            Env::None => {}
+            Env::Value(v) => vars.push((key, v)),
+            Env::Map(tag, entries) => {
+                if let Some(tag) = tag {
+                    vars.push((key.clone(), tag));
+                }
+                for (k, v) in entries {
+                    v.flatten(join_key(&key, &k), vars)?;
+                }
+            }
+            // Structs and maps can't be joined, write elements as indexed env.
+            Env::List(elements) if elements.iter().any(|v| matches!(v, Env::Map(..))) => {
+                for (idx, v) in elements.into_iter().enumerate() {
+                    v.flatten(join_key(&key, &idx.to_string()), vars)?;
+                }
+            }
+            Env::List(elements) => {
+                let value =
+                    join_list(elements, 0).map_err(|err| err.with_env(key.clone(), None))?;
+                vars.push((key, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Join list elements with the delimiter of nesting `level`.
+fn join_list(elements: Vec<Env>, level: usize) -> Result<String, Error> {
+    let delimiter = LIST_DELIMITERS.get(level).ok_or_else(|| {
+        Error::new(
+            ErrorKind::Custom,
+            format_args!("no list delimiter for nesting level {level}"),
+        )
+    })?;
+
+    let mut values = Vec::with_capacity(elements.len());
+    for v in elements {
+        let v = match v {
+            Env::None => String::new(),
+            Env::Value(v) => v,
+            Env::List(elements) => join_list(elements, level + 1)?,
+            Env::Map(..) => {
+                return Err(ser::Error::custom(
+                    "structs and maps inside nested lists are not supported",
+                ))
+            }
+        };
+        if v.contains(delimiter) {
+            return Err(Error::new(
+                ErrorKind::InvalidValue,
+                format_args!("list element contains the delimiter `{delimiter}`"),
+            ));
+        }
+        values.push(v);
+    }
+    Ok(values.join(delimiter))
+}
+
+/// Serializer of a value into [`Env`].
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Env;
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = Impossible<Env, Error>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        match std::str::from_utf8(v) {
+            Ok(v) => Ok(Env::Value(v.to_string())),
+            Err(err) => Err(ser::Error::custom(format_args!(
+                "bytes must be valid utf-8: {err}"
+            ))),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Value(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        // The variant name is the value, so only fields can be sub-keys.
+        match value.serialize(self)? {
+            Env::Map(None, entries) => Ok(Env::Map(Some(variant.to_string()), entries)),
+            _ => Err(ser::Error::custom(format_args!(
+                "newtype variant `{variant}` must contain a struct or map"
+            ))),
+        }
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SeqSerializer(Vec::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(ser::Error::custom(format_args!(
+            "tuple variant `{variant}` is not supported"
+        )))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(MapSerializer::new(None, len.unwrap_or_default()))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(MapSerializer::new(None, len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(MapSerializer::new(Some(variant.to_string()), len))
+    }
+}
+
+struct SeqSerializer(Vec<Env>);
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Env;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.0.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::List(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Env;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Env;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct MapSerializer {
+    tag: Option<String>,
+    entries: Vec<(String, Env)>,
+    last_key: Option<String>,
+}
+
+impl MapSerializer {
+    fn new(tag: Option<String>, len: usize) -> Self {
+        Self {
+            tag,
+            entries: Vec::with_capacity(len),
+            last_key: None,
+        }
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Env;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        match key.serialize(Serializer)? {
+            Env::Value(key) => {
+                self.last_key = Some(key);
+                Ok(())
+            }
+            _ => Err(ser::Error::custom("map key must be a string or number")),
+        }
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .last_key
+            .take()
+            .expect("key for current entry is missing");
+        self.entries.push((key, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Map(self.tag, self.entries))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Env;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        // Fields are written the way they are looked up when deserializing.
+        self.entries
+            .push((value::field_words(key), value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Env::Map(self.tag, self.entries))
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Env;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        ser::SerializeStruct::end(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::from_iter_with_prefix;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "lowercase")]
+    enum Backend {
+        Memory,
+        Redis { url: String, db: u8 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Config {
+        name: String,
+        debug: bool,
+        ratio: f64,
+        tags: Vec<String>,
+        pairs: Vec<(String, u16)>,
+        servers: Vec<Server>,
+        labels: BTreeMap<String, String>,
+        backend: Backend,
+        cache: Backend,
+        timeout: Option<u32>,
+    }
+
+    #[test]
+    fn test_to_env() {
+        let cfg = Config {
+            name: "app".to_string(),
+            debug: true,
+            ratio: 0.5,
+            tags: vec!["a".to_string(), "b".to_string()],
+            pairs: vec![("x".to_string(), 1), ("y".to_string(), 2)],
+            servers: vec![
+                Server {
+                    host: "a".to_string(),
+                    port: 80,
+                },
+                Server {
+                    host: "b".to_string(),
+                    port: 81,
+                },
+            ],
+            labels: BTreeMap::from([
+                ("cost_center".to_string(), "ops".to_string()),
+                ("team".to_string(), "core".to_string()),
+                ("team_name".to_string(), "infra".to_string()),
+            ]),
+            backend: Backend::Redis {
+                url: "redis://localhost".to_string(),
+                db: 1,
+            },
+            cache: Backend::Memory,
+            timeout: None,
+        };
+
+        let env = to_env_with_prefix(&cfg, "APP").expect("must succeed");
+        let expected: Vec<(String, String)> = [
+            ("APP_NAME", "app"),
+            ("APP_DEBUG", "true"),
+            ("APP_RATIO", "0.5"),
+            ("APP_TAGS", "a,b"),
+            ("APP_PAIRS", "x:1,y:2"),
+            ("APP_SERVERS_0_HOST", "a"),
+            ("APP_SERVERS_0_PORT", "80"),
+            ("APP_SERVERS_1_HOST", "b"),
+            ("APP_SERVERS_1_PORT", "81"),
+            ("APP_LABELS_COST_CENTER", "ops"),
+            ("APP_LABELS_TEAM", "core"),
+            ("APP_LABELS_TEAM_NAME", "infra"),
+            ("APP_BACKEND", "redis"),
+            ("APP_BACKEND_URL", "redis://localhost"),
+            ("APP_BACKEND_DB", "1"),
+            ("APP_CACHE", "memory"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(env, expected);
+
+        let actual: Config = from_iter_with_prefix(env, "APP").expect("must succeed");
+        assert_eq!(actual, cfg);
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Empty {
+            name: String,
+            labels: BTreeMap<String, String>,
+        }
+
+        let env = to_env(&Empty {
+            name: String::new(),
+            labels: BTreeMap::new(),
+        })
+        .expect("must succeed");
+        assert_eq!(env, vec![("NAME".to_string(), String::new())]);
+        let err = crate::from_iter::<_, _, Empty>(env).unwrap_err();
+        assert_eq!(err.to_string(), "env `NAME`: missing field `name`");
+    }
+
+    #[test]
+    fn test_to_env_errors() {
+        let err = to_env(&vec![1, 2]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "only structs and maps can be serialized into env"
+        );
+
+        let err = to_env(&BTreeMap::from([("tags", vec!["a,b"])])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "env `TAGS`: list element contains the delimiter `,`"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+/// Find the env in `names` that most likely meant to set the missing env
+/// `expected`, and return it with the name it should have.
+///
+/// Each name is compared by its leading keys split by `sep`, so that
+/// `APP_DATABSE_URL` is found for a missing `APP_DATABASE` struct, and
+/// suggested as `APP_DATABASE_URL`. Names within `max_distance` edits are
+/// candidates, and the closest one wins.
+pub(crate) fn suggest<'a, I>(
+    expected: &str,
+    names: I,
+    sep: &str,
+    max_distance: usize,
+) -> Option<(&'a str, String)>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut best: Option<(usize, &str, String)> = None;
+    for name in names {
+        for (end, _) in name.match_indices(sep).chain([(name.len(), "")]) {
+            let distance = edit_distance(expected, &name[..end]);
+            if distance == 0 || distance > max_distance {
+                continue;
+            }
+            if best.as_ref().is_none_or(|(d, _, _)| distance < *d) {
+                best = Some((distance, name, format!("{expected}{}", &name[end..])));
+            }
+        }
+    }
+    best.map(|(_, name, suggested)| (name, suggested))
+}
+
+/// Levenshtein distance between `a` and `b` in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Distances between the processed part of `a` and each prefix of `b`.
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev + usize::from(ca != *cb);
+            prev = row[j + 1];
+            row[j + 1] = substitute.min(prev + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("PORT", "PORT"), 0);
+        assert_eq!(edit_distance("PORT", "PORTS"), 1);
+        assert_eq!(edit_distance("DATABASE", "DATABSE"), 1);
+        assert_eq!(edit_distance("HOST", "HSOT"), 2);
+        assert_eq!(edit_distance("", "ABC"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let names = ["APP_DB_PORTS", "APP_DATABSE_URL", "APP_NAME"];
+
+        assert_eq!(
+            suggest("APP_DB_PORT", names, "_", 1),
+            Some(("APP_DB_PORTS", "APP_DB_PORT".to_string()))
+        );
+        assert_eq!(
+            suggest("APP_DATABASE", names, "_", 2),
+            Some(("APP_DATABSE_URL", "APP_DATABASE_URL".to_string()))
+        );
+        assert_eq!(
+            suggest("APP__DATABASE__URL", ["APP__DATABSE__URL"], "__", 2),
+            Some(("APP__DATABSE__URL", "APP__DATABASE__URL".to_string()))
+        );
+        // The closest one wins.
+        assert_eq!(
+            suggest("APP_NAMES", ["APP_NAMEXYZ", "APP_NAME"], "_", 2),
+            Some(("APP_NAME", "APP_NAMES".to_string()))
+        );
+
+        assert_eq!(suggest("APP_HOST", names, "_", 1), None);
+        assert_eq!(suggest("APP_DB", names, "_", 2), None);
+    }
+}
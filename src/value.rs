@@ -1,73 +1,215 @@
 use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fmt;
 use std::fmt::{Debug, Formatter};
-use std::{env, fmt};
+
+use crate::secret;
+
+/// Value of an env.
+#[derive(PartialEq, Clone)]
+pub(crate) enum Value {
+    /// Value that is valid unicode.
+    Str(String),
+    /// Value that is not valid unicode, kept as is with its env key so that
+    /// errors can name it.
+    Os(String, OsString),
+}
+
+impl Debug for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(v) => f.write_str(v),
+            Value::Os(_, v) => f.write_str(&v.to_string_lossy()),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Str(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Str(v)
+    }
+}
+
+impl Value {
+    /// Create value from an env that may be not valid unicode.
+    pub(crate) fn from_os(key: &str, v: OsString) -> Self {
+        match v.into_string() {
+            Ok(v) => Value::Str(v),
+            Err(v) => Value::Os(key.to_string(), v),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            Value::Str(v) => v.is_empty(),
+            Value::Os(_, v) => v.is_empty(),
+        }
+    }
+
+    /// Get value as str, `None` if it's not valid unicode.
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(v) => Some(v),
+            Value::Os(..) => None,
+        }
+    }
+
+    /// Into the raw bytes of value.
+    ///
+    /// Only unix can keep value that is not valid unicode losslessly, other
+    /// platforms replace invalid sequences with `U+FFFD`.
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Value::Str(v) => v.into_bytes(),
+            #[cfg(unix)]
+            Value::Os(_, v) => {
+                use std::os::unix::ffi::OsStringExt;
+                v.into_vec()
+            }
+            #[cfg(not(unix))]
+            Value::Os(_, v) => v.to_string_lossy().into_owned().into_bytes(),
+        }
+    }
+
+    /// Into the wide chars of value.
+    #[cfg(windows)]
+    pub(crate) fn into_wide(self) -> Vec<u16> {
+        use std::ffi::OsStr;
+        use std::os::windows::ffi::OsStrExt;
+
+        match self {
+            Value::Str(v) => OsStr::new(&v).encode_wide().collect(),
+            Value::Os(_, v) => v.encode_wide().collect(),
+        }
+    }
+}
 
 /// Node represents a tree of env values.
 ///
-/// Every env will be separated by `_` in key to construct this tree.
+/// Every env will be separated by the separator (`_` by default) in key to
+/// construct this tree.
 ///
-/// - `ABC=123` => `Node("123", {})`
-/// - `ABC_DEF=123` => `Node("", { "DEF": Node("123", {}) })`
-/// - `ABC=123,ABC_DEF=456` => `Node("123", { "DEF": Node("456", {}) })`
-#[derive(PartialEq, Clone)]
-pub(crate) struct Node(String, BTreeMap<String, Node>);
-
+/// - `ABC=123` => `Node(Some("123"), {})`
+/// - `ABC_DEF=123` => `Node(None, { "DEF": Node(Some("123"), {}) })`
+/// - `ABC=123,ABC_DEF=456` => `Node(Some("123"), { "DEF": Node(Some("456"), {}) })`
+///
+/// Value is `None` if the env is not set, and `Some("")` if it's set to
+/// empty.
+#[derive(PartialEq, Clone, Default)]
+pub(crate) struct Node(Option<Value>, BTreeMap<String, Node>);
+
+/// Node doesn't know which env is secret under the options it's
+/// deserialized with, so its debug output never shows values, only which
+/// keys are set.
 impl Debug for Node {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        if self.0.is_empty() {
-            // Value is empty, print inner map instead.
-            f.debug_map().entries(&self.1).finish()
-        } else if self.1.is_empty() {
-            // inner map is empty too, print value instead.
-            f.write_str(&self.0)
-        } else {
+        let Node(value, children) = self;
+        match value {
+            // Value is not set, print inner map instead.
+            None => f.debug_map().entries(children).finish(),
+            // inner map is empty, print value instead.
+            Some(_) if children.is_empty() => f.write_str(secret::REDACTED),
             // Print as list.
             // The first entry is value, and the second is inner map.
-            f.debug_list().entry(&self.0).entry(&self.1).finish()
+            Some(_) => f
+                .debug_list()
+                .entry(&format_args!("{}", secret::REDACTED))
+                .entry(children)
+                .finish(),
         }
     }
 }
 
 impl Node {
     /// Create a new node without children
-    pub(crate) fn new(v: impl Into<String>) -> Self {
-        Node(v.into(), BTreeMap::new())
+    pub(crate) fn new(v: impl Into<Value>) -> Self {
+        Node(Some(v.into()), BTreeMap::new())
     }
 
-    /// Get value from node.
+    /// Get value from node, empty if value is not set or not valid unicode.
     pub(crate) fn value(&self) -> &str {
-        &self.0
+        self.0.as_ref().and_then(Value::as_str).unwrap_or_default()
+    }
+
+    /// Get raw value from node, `None` if value is not set.
+    pub(crate) fn raw_value(&self) -> Option<&Value> {
+        self.0.as_ref()
     }
 
     /// Into value to get ownership.
     pub(crate) fn into_value(self) -> String {
+        match self.0 {
+            Some(Value::Str(v)) => v,
+            _ => String::new(),
+        }
+    }
+
+    /// Replace value of node, `None` to unset it.
+    pub(crate) fn set_value(&mut self, v: Option<Value>) {
+        self.0 = v;
+    }
+
+    /// Into raw value to get ownership, `None` if value is not set.
+    pub(crate) fn into_raw_value(self) -> Option<Value> {
         self.0
     }
 
+    /// Check if node has neither value nor children.
+    ///
+    /// A value that is set to empty doesn't make the node empty.
     pub(crate) fn is_empty(&self) -> bool {
-        self.0.is_empty() && self.1.is_empty()
+        self.0.is_none() && self.1.is_empty()
     }
 
     pub(crate) fn has_children(&self) -> bool {
-        self.1.contains_key(&self.0)
+        self.1.contains_key(self.value())
+    }
+
+    /// Get keys of the direct children.
+    pub(crate) fn child_keys(&self) -> BTreeSet<String> {
+        self.1.keys().cloned().collect()
     }
 
-    pub(crate) fn flatten(&self, prefix: &str) -> BTreeSet<String> {
+    /// Check if node has children keyed by array index like `0`, `1`.
+    pub(crate) fn has_indexed_children(&self) -> bool {
+        self.1.keys().any(|k| parse_index(k).is_some())
+    }
+
+    /// Take children keyed by array index, ordered by index numerically.
+    ///
+    /// Children with non-index keys are dropped.
+    pub(crate) fn into_indexed_children(self) -> Vec<(usize, Node)> {
+        let mut children: Vec<_> = self
+            .1
+            .into_iter()
+            .filter_map(|(k, v)| parse_index(&k).map(|idx| (idx, v)))
+            .collect();
+        children.sort_by_key(|(idx, _)| *idx);
+        children
+    }
+
+    pub(crate) fn flatten(&self, prefix: &str, sep: &str) -> BTreeSet<String> {
         let mut m = BTreeSet::new();
 
         for (key, value) in self.1.iter() {
             let prefix_key = if prefix.is_empty() {
                 key.to_string()
             } else {
-                format!("{prefix}_{key}")
+                format!("{prefix}{sep}{key}")
             };
 
-            if !value.0.is_empty() {
+            if value.0.is_some() {
                 m.insert(prefix_key.clone());
             }
             if !value.1.is_empty() {
                 m.insert(prefix_key.clone());
-                m.extend(value.flatten(&prefix_key))
+                m.extend(value.flatten(&prefix_key, sep))
             }
         }
 
@@ -76,96 +218,174 @@ impl Node {
 
     /// Get node value full key name
     ///
-    /// `node.get("abc_def")` => `node.get("abc").get("def")`
-    pub(crate) fn get(&self, k: &str) -> Option<&Node> {
-        match k.split_once('_') {
+    /// `node.get("abc_def", "_")` => `node.get("abc", "_").get("def", "_")`
+    pub(crate) fn get(&self, k: &str, sep: &str) -> Option<&Node> {
+        match k.split_once(sep) {
             None => self.1.get(k),
             Some((k, remain)) => match self.1.get(k) {
                 None => None,
-                Some(node) => node.get(remain),
+                Some(node) => node.get(remain, sep),
             },
         }
     }
 
+    /// Take value of node with full key name, removing the node if it has
+    /// no children.
+    pub(crate) fn take_value(&mut self, k: &str, sep: &str) -> Option<Value> {
+        let (k, v) = match k.split_once(sep) {
+            None => (k, self.1.get_mut(k)?.0.take()),
+            Some((k, remain)) => (k, self.1.get_mut(k)?.take_value(remain, sep)),
+        };
+        if self.1.get(k).is_some_and(Node::is_empty) {
+            self.1.remove(k);
+        }
+        v
+    }
+
     /// Push into node with full key name.
     ///
-    /// `node.push("abc_def", v)` => `node.push("abc", "").push("def", v)`
-    fn push(&mut self, k: &str, v: &str) {
-        match k.split_once('_') {
+    /// `node.push("abc_def", v, "_")` => `node.push("abc", "", "_").push("def", v, "_")`
+    pub(crate) fn push(&mut self, k: &str, v: impl Into<Value>, sep: &str) {
+        match k.split_once(sep) {
             None => {
-                self.1
-                    .entry(k.to_string())
-                    .or_insert_with(|| Node::new(String::default()))
-                    .0 = v.to_string();
+                self.1.entry(k.to_string()).or_default().0 = Some(v.into());
             }
             Some((k, remain)) => match self.1.get_mut(k) {
                 None => {
-                    let mut node = Self::new(String::default());
-                    node.push(remain, v);
+                    let mut node = Self::default();
+                    node.push(remain, v, sep);
                     self.1.insert(k.to_string(), node);
                 }
                 Some(node) => {
-                    node.push(remain, v);
+                    node.push(remain, v, sep);
                 }
             },
         };
     }
 
     /// Construct full tree from an iterator.
-    pub(crate) fn from_iter<Iter, S>(iter: Iter) -> Self
+    ///
+    /// Keys are lowercased unless `case_sensitive` is set. Only env that
+    /// `filter` returns `true` for with its original key and value is kept.
+    pub(crate) fn from_iter<Iter, K, V, F>(
+        iter: Iter,
+        sep: &str,
+        case_sensitive: bool,
+        mut filter: F,
+    ) -> Self
     where
-        S: AsRef<str>,
-        Iter: IntoIterator<Item = (S, S)>,
+        K: AsRef<str>,
+        V: Into<Value>,
+        Iter: IntoIterator<Item = (K, V)>,
+        F: FnMut(&str, &Value) -> bool,
     {
-        let mut root = Node::new(String::default());
+        let mut root = Node::default();
 
         let vars = iter
             .into_iter()
-            .map(|(k, v)| (k.as_ref().to_lowercase(), v))
-            .filter(|(_, v)| !v.as_ref().is_empty());
+            .map(|(k, v)| (k, v.into()))
+            .filter(|(k, v)| filter(k.as_ref(), v))
+            .map(|(k, v)| (normalize_key(k.as_ref(), case_sensitive), v));
 
         for (k, v) in vars {
-            root.push(&k, v.as_ref())
+            root.push(&k, v, sep)
         }
 
         root
     }
 
     /// Construct full tree from an iterator with prefix.
-    pub(crate) fn from_iter_with_prefix<Iter, S>(iter: Iter, prefix: &str) -> Self
+    ///
+    /// Prefix follows the same case rule as keys: it's matched case
+    /// insensitively unless `case_sensitive` is set. `filter` is only called
+    /// for env matching the prefix.
+    pub(crate) fn from_iter_with_prefix<Iter, K, V, F>(
+        iter: Iter,
+        prefix: &str,
+        sep: &str,
+        case_sensitive: bool,
+        mut filter: F,
+    ) -> Self
     where
-        S: AsRef<str>,
-        Iter: IntoIterator<Item = (S, S)>,
+        K: AsRef<str>,
+        V: Into<Value>,
+        Iter: IntoIterator<Item = (K, V)>,
+        F: FnMut(&str, &Value) -> bool,
     {
-        let prefix = format!("{}_", prefix);
-        let mut root = Node::new(String::default());
+        let prefix = format!("{prefix}{sep}");
+        let mut root = Node::default();
 
         let vars = iter.into_iter().filter_map(|(k, v)| {
-            if v.as_ref().is_empty() {
-                None
+            let key = strip_prefix(k.as_ref(), &prefix, case_sensitive)?;
+            let v = v.into();
+            if filter(k.as_ref(), &v) {
+                Some((normalize_key(key, case_sensitive), v))
             } else {
-                k.as_ref()
-                    .strip_prefix(&prefix)
-                    .map(|k| (k.to_lowercase(), v))
+                None
             }
         });
 
         for (k, v) in vars {
-            root.push(&k, v.as_ref())
+            root.push(&k, v, sep)
         }
 
         root
     }
+}
+
+/// Convert field name into lowercase words joined by `_`, so that
+/// `logLevel`, `LogLevel`, `log-level` and `LOG_LEVEL` are all `log_level`.
+///
+/// A word starts at an uppercase letter following a lowercase letter or
+/// digit, or at the last uppercase letter of an acronym like `HTTPServer`.
+pub(crate) fn field_words(field: &str) -> String {
+    let chars: Vec<char> = field.chars().collect();
+    let mut words = String::with_capacity(field.len() + 4);
+    for (idx, c) in chars.iter().enumerate() {
+        if *c == '-' {
+            words.push('_');
+            continue;
+        }
+        if c.is_uppercase() && idx > 0 {
+            let prev = chars[idx - 1];
+            let next_lower = chars.get(idx + 1).is_some_and(|c| c.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                words.push('_');
+            }
+        }
+        words.extend(c.to_lowercase());
+    }
+    words
+}
+
+fn normalize_key(k: &str, case_sensitive: bool) -> String {
+    if case_sensitive {
+        k.to_string()
+    } else {
+        k.to_lowercase()
+    }
+}
+
+/// Strip `prefix` from key, following the same case rule as keys.
+pub(crate) fn strip_prefix<'a>(k: &'a str, prefix: &str, case_sensitive: bool) -> Option<&'a str> {
+    if case_sensitive {
+        return k.strip_prefix(prefix);
+    }
 
-    /// Construct full tree from env.
-    pub(crate) fn from_env() -> Self {
-        Node::from_iter(env::vars())
+    let head = k.get(..prefix.len())?;
+    if head.to_lowercase() == prefix.to_lowercase() {
+        Some(&k[prefix.len()..])
+    } else {
+        None
     }
+}
 
-    /// Construct full tree from env with prefix.
-    pub(crate) fn from_env_with_prefix(prefix: &str) -> Self {
-        Node::from_iter_with_prefix(env::vars(), prefix)
+/// Parse key as an array index, only plain ascii digits are accepted.
+fn parse_index(k: &str) -> Option<usize> {
+    if k.is_empty() || !k.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
     }
+    k.parse().ok()
 }
 
 #[cfg(test)]
@@ -174,19 +394,19 @@ mod tests {
 
     #[test]
     fn test_get() {
-        let mut root = Node::new("");
+        let mut root = Node::default();
 
-        root.push("a_b_c_d", "Hello, World!");
-        root.push("a_b_c_e", "Hello, Mars!");
-        root.push("a_b_f", "Hello, Moon!");
+        root.push("a_b_c_d", "Hello, World!", "_");
+        root.push("a_b_c_e", "Hello, Mars!", "_");
+        root.push("a_b_f", "Hello, Moon!", "_");
 
-        assert_eq!(root.get("a_b_c_d"), Some(&Node::new("Hello, World!")));
-        assert_eq!(root.get("a_b_c_e"), Some(&Node::new("Hello, Mars!")));
-        assert_eq!(root.get("a_b_f"), Some(&Node::new("Hello, Moon!")));
+        assert_eq!(root.get("a_b_c_d", "_"), Some(&Node::new("Hello, World!")));
+        assert_eq!(root.get("a_b_c_e", "_"), Some(&Node::new("Hello, Mars!")));
+        assert_eq!(root.get("a_b_f", "_"), Some(&Node::new("Hello, Moon!")));
         assert_eq!(
-            root.get("a_b_c"),
+            root.get("a_b_c", "_"),
             Some(&Node(
-                "".to_string(),
+                None,
                 BTreeMap::from([
                     ("d".to_string(), Node::new("Hello, World!")),
                     ("e".to_string(), Node::new("Hello, Mars!"))
@@ -197,30 +417,30 @@ mod tests {
 
     #[test]
     fn test_push() {
-        let mut root = Node::new("");
+        let mut root = Node::default();
 
-        root.push("a_b_c_d", "Hello, World!");
-        root.push("a_b_c_e", "Hello, Mars!");
-        root.push("a_b_f", "Hello, Moon!");
-        root.push("a", "Hello, Earth!");
+        root.push("a_b_c_d", "Hello, World!", "_");
+        root.push("a_b_c_e", "Hello, Mars!", "_");
+        root.push("a_b_f", "Hello, Moon!", "_");
+        root.push("a", "Hello, Earth!", "_");
 
         assert_eq!(
             root,
             Node(
-                "".to_string(),
+                None,
                 BTreeMap::from([(
                     "a".to_string(),
                     Node(
-                        "Hello, Earth!".to_string(),
+                        Some(Value::from("Hello, Earth!")),
                         BTreeMap::from([(
                             "b".to_string(),
                             Node(
-                                "".to_string(),
+                                None,
                                 BTreeMap::from([
                                     (
                                         "c".to_string(),
                                         Node(
-                                            "".to_string(),
+                                            None,
                                             BTreeMap::from([
                                                 ("d".to_string(), Node::new("Hello, World!")),
                                                 ("e".to_string(), Node::new("Hello, Mars!"))
@@ -237,14 +457,69 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_take_value() {
+        let mut root = Node::default();
+        root.push("a_b", "1", "_");
+        root.push("a_b_c", "2", "_");
+        root.push("a_d_e", "3", "_");
+
+        assert_eq!(root.take_value("a_d_e", "_"), Some(Value::from("3")));
+        assert_eq!(root.take_value("a_b", "_"), Some(Value::from("1")));
+        assert_eq!(root.take_value("a_b", "_"), None);
+        assert_eq!(root.take_value("x_y", "_"), None);
+
+        // Emptied nodes are removed, while nodes with children are kept.
+        let mut expected = Node::default();
+        expected.push("a_b_c", "2", "_");
+        assert_eq!(root, expected);
+    }
+
+    #[test]
+    fn test_field_words() {
+        for field in [
+            "logLevel",
+            "LogLevel",
+            "log-level",
+            "log_level",
+            "LOG_LEVEL",
+        ] {
+            assert_eq!(field_words(field), "log_level", "{field}");
+        }
+        assert_eq!(field_words("HTTPServer"), "http_server");
+        assert_eq!(field_words("userID"), "user_id");
+        assert_eq!(field_words("ipv4Addr"), "ipv4_addr");
+        assert_eq!(field_words("Url"), "url");
+        assert_eq!(field_words("Database_Url"), "database_url");
+    }
+
+    #[test]
+    fn test_debug_hides_values() {
+        let node = Node::from_iter(
+            [
+                ("DB", "postgres"),
+                ("DB_HOST", "localhost"),
+                ("DB_PASSWORD", "hunter2"),
+                ("API_TOKEN_ID", "42"),
+            ],
+            "_",
+            false,
+            |_, _| true,
+        );
+        assert_eq!(
+            format!("{node:?}"),
+            r#"{"api": {"token": {"id": ***}}, "db": [***, {"host": ***, "password": ***}]}"#
+        );
+    }
+
     #[test]
     fn test_flatten() {
-        let mut root = Node::new("");
+        let mut root = Node::default();
 
-        root.push("a", "Hello, World!");
-        root.push("a_b_c_d", "Hello, World!");
-        root.push("a_b_c_e", "Hello, Mars!");
-        root.push("a_b_f", "Hello, Moon!");
+        root.push("a", "Hello, World!", "_");
+        root.push("a_b_c_d", "Hello, World!", "_");
+        root.push("a_b_c_e", "Hello, Mars!", "_");
+        root.push("a_b_f", "Hello, Moon!", "_");
 
         let mut expected = BTreeSet::<String>::new();
         expected.insert("a".to_owned());
@@ -254,12 +529,137 @@ mod tests {
         expected.insert("a_b_c_e".to_owned());
         expected.insert("a_b_f".to_owned());
 
-        assert_eq!(root.flatten(""), expected);
+        assert_eq!(root.flatten("", "_"), expected);
     }
     #[test]
     fn test_prefix() {
         std::env::set_var("TEST_ENV_VAR", "Hello, World!");
-        let root = Node::from_env_with_prefix("TEST_ENV");
-        assert_eq!(root.get("var"), Some(&Node::new("Hello, World!")));
+        let root = Node::from_iter_with_prefix(std::env::vars(), "TEST_ENV", "_", false, |_, v| {
+            !v.is_empty()
+        });
+        assert_eq!(root.get("var", "_"), Some(&Node::new("Hello, World!")));
+    }
+
+    #[test]
+    fn test_custom_separator() {
+        let root = Node::from_iter(
+            [
+                ("DB__MAX_CONNS", "10"),
+                ("DB__HOST", "localhost"),
+                ("LOG_LEVEL", "debug"),
+            ],
+            "__",
+            false,
+            |_, v| !v.is_empty(),
+        );
+
+        assert_eq!(root.get("db__max_conns", "__"), Some(&Node::new("10")));
+        assert_eq!(root.get("db__host", "__"), Some(&Node::new("localhost")));
+        assert_eq!(root.get("log_level", "__"), Some(&Node::new("debug")));
+        assert_eq!(root.get("log", "__"), None);
+
+        let mut expected = BTreeSet::<String>::new();
+        expected.insert("db".to_owned());
+        expected.insert("db__host".to_owned());
+        expected.insert("db__max_conns".to_owned());
+        expected.insert("log_level".to_owned());
+
+        assert_eq!(root.flatten("", "__"), expected);
+    }
+
+    #[test]
+    fn test_indexed_children() {
+        let mut root = Node::default();
+
+        root.push("2_host", "b", "_");
+        root.push("10_host", "c", "_");
+        root.push("0_host", "a", "_");
+        root.push("name", "x", "_");
+
+        assert!(root.has_indexed_children());
+        assert!(!root.get("name", "_").unwrap().has_indexed_children());
+
+        let children: Vec<_> = root
+            .into_indexed_children()
+            .into_iter()
+            .map(|(idx, node)| (idx, node.get("host", "_").unwrap().value().to_string()))
+            .collect();
+        assert_eq!(
+            children,
+            vec![
+                (0, "a".to_string()),
+                (2, "b".to_string()),
+                (10, "c".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_case_sensitive() {
+        let vars = [("Team_Name", "a"), ("TEAM_NAME", "b")];
+
+        let root = Node::from_iter(vars, "_", false, |_, v| !v.is_empty());
+        assert_eq!(root.get("team_name", "_"), Some(&Node::new("b")));
+        assert_eq!(root.get("Team_Name", "_"), None);
+
+        let root = Node::from_iter(vars, "_", true, |_, v| !v.is_empty());
+        assert_eq!(root.get("Team_Name", "_"), Some(&Node::new("a")));
+        assert_eq!(root.get("TEAM_NAME", "_"), Some(&Node::new("b")));
+        assert_eq!(root.get("team_name", "_"), None);
+    }
+
+    #[test]
+    fn test_prefix_case() {
+        let vars = [
+            ("APP_NAME", "a"),
+            ("app_Port", "1"),
+            ("ApP_Log_Level", "info"),
+        ];
+
+        let root = Node::from_iter_with_prefix(vars, "app", "_", false, |_, v| !v.is_empty());
+        assert_eq!(root.get("name", "_"), Some(&Node::new("a")));
+        assert_eq!(root.get("port", "_"), Some(&Node::new("1")));
+        assert_eq!(root.get("log_level", "_"), Some(&Node::new("info")));
+
+        let root = Node::from_iter_with_prefix(vars, "app", "_", true, |_, v| !v.is_empty());
+        assert_eq!(root.get("name", "_"), None);
+        assert_eq!(root.get("Port", "_"), Some(&Node::new("1")));
+        assert_eq!(root.get("Log_Level", "_"), None);
+    }
+
+    #[test]
+    fn test_empty_value() {
+        let vars = [("A", ""), ("B_C", "")];
+
+        let root = Node::from_iter(vars, "_", false, |_, v| !v.is_empty());
+        assert!(root.is_empty());
+
+        let root = Node::from_iter(vars, "_", false, |_, _| true);
+        assert_eq!(root.get("a", "_"), Some(&Node::new("")));
+        assert!(!root.get("a", "_").unwrap().is_empty());
+        assert!(!root.get("b", "_").unwrap().is_empty());
+        assert_eq!(root.flatten("", "_").len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_os_value() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let raw = vec![b'a', 0xff, b'b'];
+        let value = Value::from_os("A", OsString::from_vec(raw.clone()));
+        assert_eq!(value.as_str(), None);
+        assert!(!value.is_empty());
+        assert_eq!(value.clone().into_bytes(), raw);
+
+        let root = Node::from_iter([("A", value)], "_", false, |_, _| true);
+        assert_eq!(root.get("a", "_").unwrap().value(), "");
+        assert_eq!(
+            root.get("a", "_").unwrap().raw_value(),
+            Some(&Value::Os("A".to_string(), OsString::from_vec(raw)))
+        );
+
+        let value = Value::from_os("B", OsString::from("b"));
+        assert_eq!(value, Value::from("b"));
     }
 }